@@ -0,0 +1,358 @@
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, remove_file};
+use std::fs::{write, File};
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+// OPL lists apps from conf_apps.cfg, and from APPS/<name>/title.cfg since 1.1
+const APPS_DIR: &str = "APPS";
+const APPS_CONFIG: &str = "conf_apps.cfg";
+const APPS_TITLE_CONFIG: &str = "title.cfg";
+// Device OPL uses for USB drives in conf_apps.cfg paths
+const APPS_DEVICE: &str = "mass:";
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELF_HEADER_SIZE: usize = 52;
+const ELF_CLASS_32: u8 = 1;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_TYPE_EXEC: u16 = 2;
+const ELF_MACHINE_MIPS: u16 = 8;
+const ELF_FLAGS_MACH: u32 = 0x00ff0000;
+const ELF_FLAGS_MACH_5900: u32 = 0x00920000;
+
+/// Where OPL learns about the app from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppLayout {
+    /// Entry in conf_apps.cfg.
+    Config,
+    /// APPS/<name>/ folder with title.cfg.
+    Folder,
+}
+
+impl std::fmt::Display for AppLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppLayout::Config => write!(f, "{}", APPS_CONFIG),
+            AppLayout::Folder => write!(f, "{}", APPS_TITLE_CONFIG),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct App {
+    pub title: String,
+    /// Path to ELF as written in OPL configuration.
+    pub boot: String,
+    /// Local path to ELF, if it is on the same drive.
+    pub elf: Option<PathBuf>,
+    pub layout: AppLayout,
+    /// APPS/<name>/ folder of app, for folder layout only.
+    pub folder: Option<PathBuf>,
+}
+
+/// Checks that file is PlayStation 2 executable, that is MIPS R5900 ELF.
+pub fn check_elf(path: &Path) -> Result<()> {
+    let mut header = [0x00; ELF_HEADER_SIZE];
+    File::open(path)?.read_exact(&mut header)?;
+
+    let get_u16 = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+    let flags = u32::from_le_bytes([header[36], header[37], header[38], header[39]]);
+
+    let valid = header.starts_with(ELF_MAGIC)
+        && header[4] == ELF_CLASS_32
+        && header[5] == ELF_DATA_LSB
+        && get_u16(16) == ELF_TYPE_EXEC
+        && get_u16(18) == ELF_MACHINE_MIPS
+        && flags & ELF_FLAGS_MACH == ELF_FLAGS_MACH_5900;
+
+    match valid {
+        true => Ok(()),
+        false => Err(Error::from(ErrorKind::InvalidData)),
+    }
+}
+
+/// Title may not hold characters OPL uses as separators or paths cannot hold.
+pub fn check_title(title: &str) -> Result<()> {
+    let invalid = |c: char| {
+        matches!(
+            c,
+            '=' | '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'
+        )
+    };
+    // Folder layout makes directory named after title.
+    let reserved = matches!(title.trim(), "" | "." | "..");
+    if reserved || title.contains(invalid) {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    Ok(())
+}
+
+/// Parses `key=value` lines, skipping blank and malformed ones.
+fn parse_entries(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (String::from(k.trim()), String::from(v.trim())))
+        .collect()
+}
+
+fn format_entries(entries: &[(String, String)]) -> String {
+    entries
+        .iter()
+        .map(|(k, v)| format!("{}={}\n", k, v))
+        .collect()
+}
+
+fn load_config(ulpath: &Path) -> Result<Vec<(String, String)>> {
+    let path = ulpath.join(APPS_CONFIG);
+    match path.exists() {
+        true => Ok(parse_entries(&read_to_string(path)?)),
+        false => Ok(Vec::new()),
+    }
+}
+
+/// Local path of ELF from conf_apps.cfg, if it points to this drive.
+fn local_elf(ulpath: &Path, boot: &str) -> Option<PathBuf> {
+    let (device, path) = boot.split_once(":/")?;
+    match device.starts_with(APPS_DEVICE.trim_end_matches(':')) {
+        true => Some(ulpath.join(path)),
+        false => None,
+    }
+}
+
+/// Apps registered in conf_apps.cfg, followed by ones in APPS/ folders.
+pub fn find_apps(ulpath: &Path) -> Result<Vec<App>> {
+    let mut apps = load_config(ulpath)?
+        .into_iter()
+        .map(|(title, boot)| App {
+            elf: local_elf(ulpath, &boot),
+            title,
+            boot,
+            layout: AppLayout::Config,
+            folder: None,
+        })
+        .collect::<Vec<App>>();
+
+    let appsdir = ulpath.join(APPS_DIR);
+    if !appsdir.is_dir() {
+        return Ok(apps);
+    }
+
+    let mut dirs = read_dir(appsdir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join(APPS_TITLE_CONFIG).is_file())
+        .collect::<Vec<PathBuf>>();
+    dirs.sort();
+
+    for dir in dirs {
+        let entries = parse_entries(&read_to_string(dir.join(APPS_TITLE_CONFIG))?);
+        let get = |key: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.clone())
+        };
+
+        let boot = match get("boot") {
+            Some(b) => b,
+            None => continue,
+        };
+
+        apps.push(App {
+            title: get("title").unwrap_or_else(|| boot.clone()),
+            elf: Some(dir.join(&boot)),
+            boot,
+            layout: AppLayout::Folder,
+            folder: Some(dir),
+        });
+    }
+
+    Ok(apps)
+}
+
+/// Copies ELF into APPS/ and registers it under `title`.
+pub fn add_app(ulpath: &Path, elfpath: &Path, title: &str, layout: AppLayout) -> Result<App> {
+    check_title(title)?;
+    check_elf(elfpath)?;
+
+    if find_apps(ulpath)?.iter().any(|a| a.title == title) {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    let filename = elfpath
+        .file_name()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?
+        .to_string_lossy()
+        .to_string();
+
+    let (dir, boot) = match layout {
+        AppLayout::Config => {
+            let boot = format!("{}/{}/{}", APPS_DEVICE, APPS_DIR, filename);
+            (ulpath.join(APPS_DIR), boot)
+        }
+        AppLayout::Folder => (ulpath.join(APPS_DIR).join(title), filename.clone()),
+    };
+
+    let dst = dir.join(&filename);
+    if dst.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    create_dir_all(&dir)?;
+    copy(elfpath, &dst)?;
+
+    match layout {
+        AppLayout::Config => {
+            let mut entries = load_config(ulpath)?;
+            entries.push((String::from(title), boot.clone()));
+            write(ulpath.join(APPS_CONFIG), format_entries(&entries))?;
+        }
+        AppLayout::Folder => {
+            let entries = [
+                (String::from("title"), String::from(title)),
+                (String::from("boot"), boot.clone()),
+            ];
+            write(dir.join(APPS_TITLE_CONFIG), format_entries(&entries))?;
+        }
+    }
+
+    Ok(App {
+        title: String::from(title),
+        boot,
+        elf: Some(dst),
+        layout,
+        folder: match layout {
+            AppLayout::Config => None,
+            AppLayout::Folder => Some(dir),
+        },
+    })
+}
+
+/// Unregisters app and deletes its files from this drive.
+pub fn remove_app(ulpath: &Path, app: &App) -> Result<()> {
+    match app.layout {
+        AppLayout::Config => {
+            let mut entries = load_config(ulpath)?;
+            entries.retain(|(title, _)| *title != app.title);
+            write(ulpath.join(APPS_CONFIG), format_entries(&entries))?;
+
+            // Only ELF put into APPS/ by `add_app` goes, and only if no other
+            // entry still boots it.
+            let appsdir = ulpath.join(APPS_DIR);
+            let shared = |elf: &PathBuf| {
+                entries
+                    .iter()
+                    .any(|(_, boot)| local_elf(ulpath, boot).as_ref() == Some(elf))
+            };
+            let removable = app
+                .elf
+                .as_ref()
+                .filter(|p| p.parent() == Some(appsdir.as_path()) && p.file_name().is_some())
+                .filter(|p| p.is_file() && !shared(p));
+
+            if let Some(elf) = removable {
+                remove_file(elf)?;
+            }
+        }
+        AppLayout::Folder => {
+            if let Some(folder) = &app.folder {
+                remove_dir_all(folder)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+
+    fn make_elf(path: &Path, flags: u32) {
+        let mut header = vec![0x00; ELF_HEADER_SIZE];
+        header[..4].copy_from_slice(ELF_MAGIC);
+        header[4] = ELF_CLASS_32;
+        header[5] = ELF_DATA_LSB;
+        header[16..18].copy_from_slice(&ELF_TYPE_EXEC.to_le_bytes());
+        header[18..20].copy_from_slice(&ELF_MACHINE_MIPS.to_le_bytes());
+        header[36..40].copy_from_slice(&flags.to_le_bytes());
+        write(path, header).unwrap();
+    }
+
+    #[test]
+    fn test_check_elf() {
+        let dir = TestDir::new("check_elf");
+        let path = dir.join("CHECK.ELF");
+        make_elf(&path, 0x20924001);
+        assert!(check_elf(&path).is_ok());
+        make_elf(&path, 0x50001001);
+        assert!(check_elf(&path).is_err());
+        write(&path, b"not an elf").unwrap();
+        assert!(check_elf(&path).is_err());
+    }
+
+    #[test]
+    fn test_check_title() {
+        assert!(check_title("uLaunchELF 4.43").is_ok());
+        for title in ["", " ", ".", "..", " .. ", "a/b", "a=b"] {
+            assert!(check_title(title).is_err());
+        }
+    }
+
+    #[test]
+    fn test_remove_app_keeps_shared_files() {
+        let dir = TestDir::new("apps_shared");
+        let ulpath = dir.path();
+        create_dir_all(ulpath.join("APPS")).unwrap();
+        create_dir_all(ulpath.join("BOOT")).unwrap();
+        write(ulpath.join("APPS").join("ULE.ELF"), b"elf").unwrap();
+        write(ulpath.join("BOOT").join("BOOT.ELF"), b"elf").unwrap();
+        write(
+            ulpath.join(APPS_CONFIG),
+            concat!(
+                "uLE=mass:/APPS/ULE.ELF\n",
+                "uLE copy=mass:/APPS/ULE.ELF\n",
+                "Boot=mass:/BOOT/BOOT.ELF\n"
+            ),
+        )
+        .unwrap();
+
+        let apps = find_apps(ulpath).unwrap();
+        remove_app(ulpath, &apps[0]).unwrap();
+        assert!(ulpath.join("APPS").join("ULE.ELF").exists());
+        remove_app(ulpath, &apps[2]).unwrap();
+        assert!(ulpath.join("BOOT").join("BOOT.ELF").exists());
+        remove_app(ulpath, &apps[1]).unwrap();
+        assert!(!ulpath.join("APPS").join("ULE.ELF").exists());
+        assert!(find_apps(ulpath).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_and_remove_apps() {
+        let dir = TestDir::new("apps");
+        let ulpath = dir.join("usb");
+        let elfpath = dir.join("ULE.ELF");
+        create_dir_all(&ulpath).unwrap();
+        make_elf(&elfpath, 0x20924001);
+
+        let app = add_app(&ulpath, &elfpath, "uLaunchELF", AppLayout::Config).unwrap();
+        assert_eq!(app.boot, "mass:/APPS/ULE.ELF");
+        let err = add_app(&ulpath, &elfpath, "uLE", AppLayout::Config).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        add_app(&ulpath, &elfpath, "uLE", AppLayout::Folder).unwrap();
+
+        let apps = find_apps(&ulpath).unwrap();
+        let layouts = apps.iter().map(|a| a.layout).collect::<Vec<_>>();
+        assert_eq!(layouts, vec![AppLayout::Config, AppLayout::Folder]);
+        assert!(apps.iter().all(|a| a.elf.as_ref().unwrap().exists()));
+
+        for app in &apps {
+            remove_app(&ulpath, app).unwrap();
+        }
+        assert!(find_apps(&ulpath).unwrap().is_empty());
+        assert!(!ulpath.join("APPS").join("ULE.ELF").exists());
+    }
+}
@@ -1,30 +1,144 @@
+use crate::game::classify::{classify, ImageKind};
+use crate::game::iso::{read_layers, read_serial, read_volume_label, Layers};
+use crate::game::name::OPL_NAME_SIZE;
+use crate::game::name::{make_name, normalize_name, region_from_serial, NameFields};
+use crate::game::preflight::{preflight, Defect};
+use crate::game::source::stream::StreamImage;
+use crate::game::source::with_image_start;
+use crate::game::titles::TitleDb;
+use crate::settings::Settings;
 use crate::ul::Ulcfg;
 
 use std::fs::metadata;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{stdin, Error, ErrorKind, Result};
 use std::path::Path;
 
 use clap::ArgMatches;
 
-fn add_game(isopath: &Path, dstpath: &Path, name: String) -> Result<()> {
-    let ulpath = dstpath.join(Path::new("ul.cfg"));
-    let dstspace = fs2::available_space(&dstpath)?;
-    let iso = metadata(&isopath)?;
+const STDIN_IMAGE: &str = "-";
+// Naming after database title is template with title alone.
+const DB_NAME_TEMPLATE: &str = "{title}";
 
-    if iso.len() >= dstspace {
-        return Err(Error::from(ErrorKind::OutOfMemory));
+fn load_ulcfg(ulpath: &Path) -> Result<Ulcfg> {
+    match ulpath.exists() {
+        true => Ulcfg::load(ulpath),
+        false => Ok(Ulcfg::new()),
     }
+}
 
-    if name.len() > 32 {
-        // OPL name cannot be longer than 32 bytes
+pub fn check_name(name: &str) -> Result<()> {
+    if name.len() > OPL_NAME_SIZE {
         return Err(Error::from(ErrorKind::InvalidInput));
     }
 
-    let mut ulcfg = match ulpath.exists() {
-        true => Ulcfg::load(&ulpath)?,
-        false => Ulcfg::new(),
+    Ok(())
+}
+
+/// Refuses images OPL cannot run from USB, explaining why.
+fn check_kind(kind: ImageKind) -> Result<()> {
+    match kind {
+        ImageKind::Ps2Dvd | ImageKind::Ps2Cd => return Ok(()),
+        ImageKind::Ps1 => println!(concat!(
+            "This is PlayStation 1 disc, which OPL runs through POPStarter instead.\n",
+            "Convert it to .VCD (e.g. with cue2pops) and put it into POPS/ folder."
+        )),
+        ImageKind::DvdVideo => println!("This is DVD-Video disc, not PlayStation 2 game"),
+        ImageKind::Unknown => {
+            println!("This does not look like PlayStation 2 game, SYSTEM.CNF with BOOT2 is missing")
+        }
+    }
+
+    Err(Error::from(ErrorKind::InvalidData))
+}
+
+/// What `add` reads from image before splitting it.
+///
+/// It is all read in one go, so archives are decompressed only once for it.
+struct ImageFacts {
+    kind: ImageKind,
+    defects: Result<Vec<Defect>>,
+    layers: Option<Layers>,
+    serial: Result<String>,
+    label: Option<String>,
+    /// Size of image, or of archive when it does not tell.
+    size: u64,
+}
+
+fn read_facts(isopath: &Path) -> Result<ImageFacts> {
+    with_image_start(isopath, |mut start| {
+        let kind = classify(&mut start.image, start.media)?;
+
+        // Gzip and xz do not tell image size, so it cannot be checked.
+        let defects = match start.size {
+            Some(size) => preflight(&mut start.image, size),
+            None => Ok(Vec::new()),
+        };
+        let layers = match (start.whole, start.size) {
+            (true, Some(size)) => read_layers(&mut start.image, size).ok().flatten(),
+            _ => None,
+        };
+
+        let size = match start.size {
+            Some(size) => size,
+            None => metadata(isopath)?.len(),
+        };
+
+        Ok(ImageFacts {
+            kind,
+            defects,
+            layers,
+            serial: read_serial(&mut start.image),
+            label: read_volume_label(&mut start.image).ok(),
+            size,
+        })
+    })
+}
+
+/// Looks for signs of truncated or corrupt image before it is split.
+fn check_image(facts: &ImageFacts) -> Result<()> {
+    let defects = match &facts.defects {
+        Ok(defects) => defects,
+        Err(e) => return Err(Error::from(e.kind())),
     };
 
+    if defects.is_empty() {
+        return Ok(());
+    }
+
+    for defect in defects {
+        println!("{}", defect);
+    }
+
+    println!("Image looks damaged, use --force to add it anyway");
+    Err(Error::from(ErrorKind::InvalidData))
+}
+
+/// Warns about dual-layer images OPL may not read in USB mode.
+fn check_layers(facts: &ImageFacts) {
+    if facts
+        .layers
+        .as_ref()
+        .is_some_and(|l| l.layer_break.is_none())
+    {
+        println!(concat!(
+            "Warning: dual-layer image has no second layer volume descriptor,\n",
+            "OPL may fail to read data past layer break from USB"
+        ));
+    }
+}
+
+fn add_game(isopath: &Path, dstpath: &Path, name: String, size: u64) -> Result<()> {
+    let ulpath = dstpath.join(Path::new("ul.cfg"));
+    let dstspace = fs2::available_space(&dstpath)?;
+
+    // Archives are checked by size of image inside, not their own.
+    if size >= dstspace {
+        return Err(Error::from(ErrorKind::OutOfMemory));
+    }
+
+    check_name(&name)?;
+    let mut ulcfg = load_ulcfg(&ulpath)?;
+
     let isoname = isopath.file_name().unwrap().to_str().unwrap();
     println!("Creating {} from {}", name, isoname);
     ulcfg.add_game(isopath, dstpath, name)?;
@@ -33,18 +147,169 @@ fn add_game(isopath: &Path, dstpath: &Path, name: String) -> Result<()> {
     Ok(())
 }
 
+fn add_game_from_stdin(dstpath: &Path, name: String, size: Option<u64>) -> Result<()> {
+    let ulpath = dstpath.join(Path::new("ul.cfg"));
+    let dstspace = fs2::available_space(dstpath)?;
+    check_name(&name)?;
+
+    let stream = StreamImage::new(Box::new(stdin()), size)?;
+    check_kind(classify(stream.prefix(), stream.media())?)?;
+    if stream.size().is_some_and(|s| s >= dstspace) {
+        return Err(Error::from(ErrorKind::OutOfMemory));
+    }
+
+    let mut ulcfg = load_ulcfg(&ulpath)?;
+
+    println!("Creating {} from stdin", name);
+    ulcfg.add_game_from_stream(stream, dstpath, name)?;
+    ulcfg.save(&ulpath)?;
+
+    Ok(())
+}
+
+fn name_from_file(isopath: &Path) -> String {
+    // Archived images like foo.iso.gz are named after inner .iso.
+    let mut isoname = Path::new(isopath.file_stem().unwrap());
+    if isoname
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("iso"))
+    {
+        isoname = Path::new(isoname.file_stem().unwrap());
+    }
+
+    String::from(isoname.to_str().unwrap())
+}
+
+fn name_from_template(
+    isopath: &Path,
+    facts: &ImageFacts,
+    template: &str,
+    titles: Option<&Path>,
+) -> Result<String> {
+    let serial = match &facts.serial {
+        Ok(serial) => serial.clone(),
+        Err(e) => return Err(Error::from(e.kind())),
+    };
+    let stem = name_from_file(isopath);
+
+    let title = match titles {
+        Some(db) => TitleDb::load(db)?.get(&serial).map(|e| e.title.clone()),
+        None => None,
+    };
+
+    if title.is_none() && titles.is_some() {
+        println!("No title for {} in database, using file name", serial);
+    }
+
+    let fields = NameFields {
+        title: title.unwrap_or_else(|| stem.clone()),
+        region: String::from(region_from_serial(&serial).unwrap_or_default()),
+        label: facts.label.clone().unwrap_or_default(),
+        serial,
+        stem,
+    };
+
+    make_name(template, &fields).inspect_err(|e| {
+        if e.kind() == ErrorKind::InvalidInput {
+            println!(
+                "Name template can use {{stem}}, {{serial}}, {{region}}, {{label}} and {{title}}"
+            );
+        }
+    })
+}
+
+/// Makes name OPL can show, unless `strict` asks to take it as given.
+pub fn prepare_name(name: String, strict: bool) -> Result<String> {
+    if strict {
+        check_name(&name)?;
+        return Ok(name);
+    }
+
+    let normalized = normalize_name(&name);
+    if normalized.is_empty() {
+        println!("Name {} has no characters OPL can show", name);
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    if normalized != name {
+        println!("Name {} changed to {} for OPL", name, normalized);
+    }
+
+    Ok(normalized)
+}
+
 pub fn add(args: &ArgMatches) {
-    let isopath = Path::new(args.value_of("image").unwrap());
+    let image = args.value_of("image").unwrap();
+    let isopath = Path::new(image);
     let dstpath = Path::new(args.value_of("ulpath").unwrap());
-    let opl_name = match args.value_of("name") {
-        Some(n) => String::from(n),
-        None => {
-            let isoname = isopath.file_stem().unwrap();
-            String::from(isoname.to_str().unwrap())
+
+    let size = match args.value_of("size").map(|s| s.parse::<u64>()) {
+        Some(Ok(s)) => Some(s),
+        Some(Err(_)) => {
+            println!("Image size must be given in bytes");
+            return;
         }
+        None => None,
     };
 
-    match add_game(isopath, dstpath, opl_name) {
+    // Images are checked first, as naming them may need their serial.
+    let facts = match image {
+        STDIN_IMAGE => None,
+        _ => match read_facts(isopath) {
+            Ok(facts) => Some(facts),
+            Err(_) => {
+                println!("Could not create the game");
+                return;
+            }
+        },
+    };
+
+    if let Some(facts) = &facts {
+        let force = args.is_present("force");
+        if check_kind(facts.kind).is_err() || (!force && check_image(facts).is_err()) {
+            println!("Could not create the game");
+            return;
+        }
+
+        // Layout is only warned about, as the image may still work.
+        check_layers(facts);
+    }
+
+    let name = match (args.value_of("name"), &facts) {
+        (Some(n), _) => Ok(String::from(n)),
+        (None, None) => {
+            println!("Name has to be given with -n when reading image from stdin");
+            return;
+        }
+        (None, Some(facts)) => {
+            let template = match (args.value_of("name-template"), args.value_of("name-from")) {
+                (Some(t), _) => Some(String::from(t)),
+                (None, Some("db")) => Some(String::from(DB_NAME_TEMPLATE)),
+                (None, Some(_)) => None,
+                (None, None) => Settings::load().name_template,
+            };
+
+            let titles = args.value_of("titles").map(Path::new);
+            if args.value_of("name-from") == Some("db") && titles.is_none() {
+                println!("Title database has to be given with --titles");
+                return;
+            }
+
+            match template {
+                Some(t) => name_from_template(isopath, facts, &t, titles),
+                None => Ok(name_from_file(isopath)),
+            }
+        }
+    };
+
+    let result = name
+        .and_then(|n| prepare_name(n, args.is_present("strict")))
+        .and_then(|n| match &facts {
+            None => add_game_from_stdin(dstpath, n, size),
+            Some(facts) => add_game(isopath, dstpath, n, facts.size),
+        });
+
+    match result {
         Ok(()) => (),
         Err(_) => println!("Could not create the game"),
     }
@@ -0,0 +1,143 @@
+use crate::apps::{add_app, find_apps, remove_app, App, AppLayout};
+use crate::ul::table;
+
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+fn app_status(app: &App) -> &'static str {
+    match &app.elf {
+        Some(elf) if elf.exists() => "OK",
+        Some(_) => "Missing",
+        // ELF lives on memory card or other device.
+        None => "Elsewhere",
+    }
+}
+
+fn list_apps(ulpath: &Path) -> Result<()> {
+    let apps = find_apps(ulpath)?;
+    let rows = apps
+        .iter()
+        .enumerate()
+        .map(|(pos, app)| {
+            vec![
+                pos.to_string(),
+                app.title.clone(),
+                app.layout.to_string(),
+                app.boot.clone(),
+                String::from(app_status(app)),
+            ]
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    let col_names = vec![
+        String::from("Index"),
+        String::from("Title"),
+        String::from("Source"),
+        String::from("Boot"),
+        String::from("Status"),
+    ];
+
+    // Titles and paths have no fixed length, so columns fit the longest one.
+    let col_sizes = (0..col_names.len())
+        .map(|col| {
+            rows.iter()
+                .map(|r| r[col].len())
+                .chain(std::iter::once(col_names[col].len()))
+                .max()
+                .unwrap()
+        })
+        .collect::<Vec<usize>>();
+
+    let hline = table::make_hline(&col_sizes);
+    println!("{}", hline);
+    println!("{}", table::make_row(col_names, &col_sizes));
+    println!("{}", hline);
+
+    for row in rows {
+        println!("{}", table::make_row(row, &col_sizes));
+    }
+
+    println!("{}", hline);
+
+    Ok(())
+}
+
+fn select_app(apps: Vec<App>, app: &str) -> Option<App> {
+    let index = match app.parse::<usize>() {
+        Ok(i) if i < apps.len() => i,
+        _ => apps.iter().position(|a| a.title == app)?,
+    };
+
+    apps.into_iter().nth(index)
+}
+
+fn add(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let elfpath = Path::new(args.value_of("elf").unwrap());
+    let title = match args.value_of("name") {
+        Some(n) => String::from(n),
+        None => elfpath
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    let layout = match args.is_present("folder") {
+        true => AppLayout::Folder,
+        false => AppLayout::Config,
+    };
+
+    match add_app(ulpath, elfpath, &title, layout) {
+        Ok(app) => println!("Added {} as {}", app.title, app.boot),
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            println!(
+                "{} is not PlayStation 2 (MIPS R5900) ELF",
+                elfpath.display()
+            )
+        }
+        Err(e) if e.kind() == ErrorKind::InvalidInput => {
+            println!("App title must not be empty or contain = / \\ : * ? \" < > |")
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            println!("App with the same title or ELF name already exists")
+        }
+        Err(_) => println!("Could not add the app"),
+    }
+}
+
+fn list(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+
+    match list_apps(ulpath) {
+        Ok(()) => (),
+        Err(_) => println!("Could not list apps"),
+    }
+}
+
+fn remove_selected(ulpath: &Path, app: &str) -> Result<()> {
+    let app =
+        select_app(find_apps(ulpath)?, app).ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    println!("Removing {}", app.title);
+    remove_app(ulpath, &app)
+}
+
+fn remove(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let app = args.value_of("app").unwrap();
+
+    match remove_selected(ulpath, app) {
+        Ok(()) => (),
+        Err(_) => println!("Could not remove the app"),
+    }
+}
+
+pub fn apps(args: &ArgMatches) {
+    match args.subcommand() {
+        ("add", Some(args)) => add(args),
+        ("list", Some(args)) => list(args),
+        ("remove", Some(args)) => remove(args),
+        _ => (),
+    }
+}
@@ -0,0 +1,70 @@
+use crate::game::art::{convert_art, find_art, normalize_key};
+use crate::ul::Ulcfg;
+
+use std::io::Result;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+fn import_art(ulpath: &Path, dir: &Path) -> Result<()> {
+    let ulcfg = Ulcfg::load(&ulpath.join(Path::new("ul.cfg")))?;
+
+    // Games without readable serial have nowhere to put their art.
+    let games = (0..ulcfg.num_games())
+        .filter_map(|i| {
+            let serial = ulcfg.game_serial(i).ok()?;
+            Some((ulcfg.game_name(i).unwrap(), serial))
+        })
+        .collect::<Vec<_>>();
+
+    for file in find_art(dir)? {
+        let filename = file.path.file_name().unwrap().to_string_lossy().to_string();
+        let key = normalize_key(&file.key);
+        let game = games
+            .iter()
+            .find(|(name, serial)| normalize_key(serial) == key || normalize_key(name) == key);
+
+        let (name, serial) = match game {
+            Some(g) => g,
+            None => {
+                println!("Skipping {}: no matching game", filename);
+                continue;
+            }
+        };
+
+        if file.kind.exists(ulpath, serial) {
+            println!(
+                "Skipping {}: {} art already exists",
+                filename, file.kind.suffix
+            );
+            continue;
+        }
+
+        println!(
+            "Adding {} art for {} from {}",
+            file.kind.suffix, name, filename
+        );
+        let dstpath = file.kind.path(ulpath, serial);
+        if convert_art(&file.path, &dstpath, &file.kind).is_err() {
+            println!("Could not convert {}", filename);
+        }
+    }
+
+    Ok(())
+}
+
+fn import(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let dir = Path::new(args.value_of("dir").unwrap());
+
+    match import_art(ulpath, dir) {
+        Ok(()) => (),
+        Err(_) => println!("Could not import art"),
+    }
+}
+
+pub fn art(args: &ArgMatches) {
+    if let ("import", Some(args)) = args.subcommand() {
+        import(args);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::game::cheats::{find_cheats, CheatFile};
+use crate::game::config::GameConfig;
+use crate::ul::Ulcfg;
+
+use std::fs::read;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+fn load_game(ulpath: &Path, game: &str) -> Result<(Ulcfg, usize, String)> {
+    let ulcfg = Ulcfg::load(&ulpath.join(Path::new("ul.cfg")))?;
+    let index = ulcfg
+        .select_game(game)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    let serial = ulcfg.game_serial(index)?;
+
+    Ok((ulcfg, index, serial))
+}
+
+fn print_groups(cheats: &CheatFile) {
+    for (number, group) in cheats.groups.iter().enumerate() {
+        let mark = match group.enabled {
+            true => 'x',
+            false => ' ',
+        };
+
+        println!(
+            "  [{}] {}: {} ({} codes)",
+            mark,
+            number + 1,
+            group.name,
+            group.codes.len()
+        );
+    }
+}
+
+fn add_cheats(ulpath: &Path, game: &str, dbpath: &Path) -> Result<()> {
+    let (ulcfg, index, serial) = load_game(ulpath, game)?;
+    let name = ulcfg.game_name(index).unwrap();
+
+    // Cheat lists floating around are not always valid UTF-8.
+    let db = String::from_utf8_lossy(&read(dbpath)?).to_string();
+    let mut cheats = find_cheats(&db, &serial, name).ok_or_else(|| {
+        println!(
+            "No cheats for {} ({}) in {}",
+            name,
+            serial,
+            dbpath.display()
+        );
+        Error::from(ErrorKind::NotFound)
+    })?;
+    if cheats.is_encrypted() {
+        println!(
+            "Cheats for {} are encrypted CodeBreaker v7 codes, OPL needs raw ones",
+            name
+        );
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    cheats.serial = Some(serial.clone());
+
+    // Reinstalled cheats keep groups user has disabled before.
+    let chtpath = CheatFile::path(ulpath, &serial);
+    if let Ok(installed) = CheatFile::load(&chtpath) {
+        for group in cheats.groups.iter_mut() {
+            if let Some(old) = installed.groups.iter().find(|g| g.name == group.name) {
+                group.enabled = old.enabled;
+            }
+        }
+    }
+
+    cheats.save(&chtpath)?;
+
+    let cfgpath = GameConfig::path(ulpath, &serial);
+    let mut config = GameConfig::load(&cfgpath)?;
+    config.set_cheats(true);
+    config.save(&cfgpath)?;
+
+    println!("Added cheats for {} ({})", name, serial);
+    print_groups(&cheats);
+
+    Ok(())
+}
+
+fn list_cheats(ulpath: &Path, game: Option<&str>) -> Result<()> {
+    let ulcfg = Ulcfg::load(&ulpath.join(Path::new("ul.cfg")))?;
+    let indices = match game {
+        Some(g) => vec![ulcfg
+            .select_game(g)
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?],
+        None => (0..ulcfg.num_games()).collect(),
+    };
+
+    for index in indices {
+        let serial = match ulcfg.game_serial(index) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+
+        let chtpath = CheatFile::path(ulpath, &serial);
+        if !chtpath.exists() {
+            continue;
+        }
+
+        println!(
+            "{}: {} ({})",
+            index,
+            ulcfg.game_name(index).unwrap(),
+            serial
+        );
+        print_groups(&CheatFile::load(&chtpath)?);
+    }
+
+    Ok(())
+}
+
+fn toggle_cheats<'a>(
+    ulpath: &Path,
+    game: &str,
+    groups: impl Iterator<Item = &'a str>,
+) -> Result<()> {
+    let (ulcfg, index, serial) = load_game(ulpath, game)?;
+    let chtpath = CheatFile::path(ulpath, &serial);
+    let mut cheats = CheatFile::load(&chtpath)?;
+
+    for group in groups {
+        let number = cheats.select_group(group).ok_or_else(|| {
+            println!("No cheat named {}", group);
+            Error::from(ErrorKind::NotFound)
+        })?;
+        let group = &mut cheats.groups[number];
+        group.enabled = !group.enabled;
+    }
+
+    cheats.save(&chtpath)?;
+
+    println!("{} ({})", ulcfg.game_name(index).unwrap(), serial);
+    print_groups(&cheats);
+
+    Ok(())
+}
+
+fn add(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let game = args.value_of("game").unwrap();
+    let dbpath = Path::new(args.value_of("db").unwrap());
+
+    match add_cheats(ulpath, game, dbpath) {
+        Ok(()) => (),
+        Err(_) => println!("Could not add cheats"),
+    }
+}
+
+fn list(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+
+    match list_cheats(ulpath, args.value_of("game")) {
+        Ok(()) => (),
+        Err(_) => println!("Could not list cheats"),
+    }
+}
+
+fn toggle(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let game = args.value_of("game").unwrap();
+    let groups = args.values_of("cheat").unwrap();
+
+    match toggle_cheats(ulpath, game, groups) {
+        Ok(()) => (),
+        Err(_) => println!("Could not toggle cheats"),
+    }
+}
+
+pub fn cheats(args: &ArgMatches) {
+    match args.subcommand() {
+        ("add", Some(args)) => add(args),
+        ("list", Some(args)) => list(args),
+        ("toggle", Some(args)) => toggle(args),
+        _ => (),
+    }
+}
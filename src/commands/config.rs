@@ -0,0 +1,60 @@
+use crate::game::config::{parse_modes, parse_vmc, GameConfig};
+use crate::ul::Ulcfg;
+
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+fn configure_game(path: &Path, game: &str, args: &ArgMatches) -> Result<()> {
+    let ulpath = path.join(Path::new("ul.cfg"));
+    let ulcfg = Ulcfg::load(&ulpath)?;
+    let index = ulcfg
+        .select_game(game)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    let serial = ulcfg.game_serial(index)?;
+
+    let cfgpath = GameConfig::path(path, &serial);
+    let mut config = GameConfig::load(&cfgpath)?;
+    let mut changed = false;
+
+    if let Some(modes) = args.value_of("mode") {
+        config.set_modes(&parse_modes(modes)?)?;
+        changed = true;
+    }
+
+    for vmc in args.values_of("vmc").into_iter().flatten() {
+        let (slot, name) = parse_vmc(vmc)?;
+        config.set_vmc(slot, &name)?;
+        changed = true;
+    }
+
+    for entry in args.values_of("set").into_iter().flatten() {
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+        config.set(key, value);
+        changed = true;
+    }
+
+    if changed {
+        config.save(&cfgpath)?;
+    }
+
+    println!("{} ({})", ulcfg.game_name(index).unwrap(), serial);
+    for line in config.describe() {
+        println!("  {}", line);
+    }
+
+    Ok(())
+}
+
+pub fn config(args: &ArgMatches) {
+    let path = Path::new(args.value_of("ulpath").unwrap());
+    let game = args.value_of("game").unwrap();
+
+    match configure_game(path, game, args) {
+        Ok(()) => (),
+        Err(_) => println!("Could not configure the game"),
+    }
+}
@@ -0,0 +1,66 @@
+use crate::ul::Ulcfg;
+
+use std::fs::create_dir_all;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+fn export_games(path: &Path, target: &Path, selected: Option<usize>) -> Result<()> {
+    let ulpath = path.join(Path::new("ul.cfg"));
+    let ulcfg = Ulcfg::load(&ulpath)?;
+
+    let indices = match selected {
+        Some(index) if index < ulcfg.num_games() => vec![index],
+        Some(_) => return Err(Error::from(ErrorKind::InvalidInput)),
+        None => (0..ulcfg.num_games()).collect(),
+    };
+
+    create_dir_all(target)?;
+    let dstspace = fs2::available_space(target)?;
+    let total: u64 = indices.iter().map(|i| ulcfg.game_size(*i)).sum();
+
+    if total >= dstspace {
+        return Err(Error::from(ErrorKind::OutOfMemory));
+    }
+
+    let mut failed = 0;
+    for index in indices {
+        if ulcfg.export_game(index, target).is_err() {
+            failed += 1;
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::from(ErrorKind::Other)),
+    }
+}
+
+pub fn export(args: &ArgMatches) {
+    let path = Path::new(args.value_of("ulpath").unwrap());
+    let target = Path::new(args.value_of("target").unwrap());
+
+    let selected = match (args.value_of("index"), args.value_of("name")) {
+        (Some(index), _) => match index.parse::<usize>() {
+            Ok(i) => Some(i),
+            Err(_) => {
+                println!("Index must be a number");
+                return;
+            }
+        },
+        (None, Some(name)) => match Ulcfg::load(&path.join("ul.cfg")).map(|u| u.find_game(name)) {
+            Ok(Some(i)) => Some(i),
+            _ => {
+                println!("Could not find game by name");
+                return;
+            }
+        },
+        (None, None) => None,
+    };
+
+    match export_games(path, target, selected) {
+        Ok(()) => (),
+        Err(_) => println!("Could not export all games"),
+    }
+}
@@ -0,0 +1,177 @@
+use crate::commands::add::check_name;
+use crate::game::source::archive;
+use crate::game::source::stream::StreamImage;
+use crate::hdd::hdl::HdlHeader;
+use crate::hdd::HddImage;
+use crate::ul::Ulcfg;
+
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+const GIGABYTE: u64 = 1024 * 1024 * 1024;
+
+fn init_image(path: &Path, size_gb: u64) -> Result<()> {
+    HddImage::create(path, size_gb * GIGABYTE)?;
+    println!("Created {}GB APA disk image {}", size_gb, path.display());
+    Ok(())
+}
+
+fn install_game(path: &Path, isopath: &Path, name: String) -> Result<()> {
+    check_name(&name)?;
+
+    // Slices are sized from whole image, which streams cannot tell upfront.
+    if isopath == Path::new("-") || archive::detect(isopath)?.is_some() {
+        println!("Image has to be extracted before it is installed on disk image");
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    let mut hdd = HddImage::open(path)?;
+
+    let isoname = isopath.file_name().unwrap().to_str().unwrap();
+    println!("Installing {} from {}", name, isoname);
+    hdd.install_game(isopath, &name)
+}
+
+fn find_game(hdd: &mut HddImage, index: Option<usize>, name: Option<&str>) -> Result<HdlHeader> {
+    let games = hdd.games()?;
+    let found = match (index, name) {
+        (Some(i), _) => games.into_iter().nth(i),
+        (None, Some(n)) => games.into_iter().find(|g| g.name == n),
+        (None, None) => None,
+    };
+
+    found.ok_or_else(|| Error::from(ErrorKind::NotFound))
+}
+
+fn extract_to_iso(hdd: &mut HddImage, game: &HdlHeader, isopath: &Path) -> Result<()> {
+    let mut iso = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(isopath)?;
+
+    println!("Extracting {} to {}", game.name, isopath.display());
+    std::io::copy(&mut hdd.game_reader(game), &mut iso)?;
+    Ok(())
+}
+
+fn extract_to_ul(hdd: &mut HddImage, game: &HdlHeader, dstpath: &Path) -> Result<()> {
+    let ulpath = dstpath.join(Path::new("ul.cfg"));
+    check_name(&game.name)?;
+
+    if game.size() >= fs2::available_space(dstpath)? {
+        return Err(Error::from(ErrorKind::OutOfMemory));
+    }
+
+    let mut ulcfg = match ulpath.exists() {
+        true => Ulcfg::load(&ulpath)?,
+        false => Ulcfg::new(),
+    };
+
+    let reader = hdd.game_reader(game);
+    let stream = StreamImage::new(Box::new(reader), Some(game.size()))?.with_media(game.media);
+
+    println!("Creating {} from disk image", game.name);
+    ulcfg.add_game_from_stream(stream, dstpath, game.name.clone())?;
+    ulcfg.save(&ulpath)?;
+
+    Ok(())
+}
+
+fn extract_game(
+    path: &Path,
+    target: &Path,
+    index: Option<usize>,
+    name: Option<&str>,
+    ul: bool,
+) -> Result<()> {
+    let mut hdd = HddImage::open_read_only(path)?;
+    let game = find_game(&mut hdd, index, name)?;
+
+    match ul {
+        true => extract_to_ul(&mut hdd, &game, target),
+        false => extract_to_iso(&mut hdd, &game, target),
+    }
+}
+
+fn init(args: &ArgMatches) {
+    let path = Path::new(args.value_of("image").unwrap());
+    let size = match args.value_of("size").unwrap().parse::<u64>() {
+        Ok(s) => s,
+        Err(_) => {
+            println!("Disk size must be given in whole gigabytes");
+            return;
+        }
+    };
+
+    match init_image(path, size) {
+        Ok(()) => (),
+        Err(_) => println!("Could not create the disk image"),
+    }
+}
+
+fn install(args: &ArgMatches) {
+    let path = Path::new(args.value_of("image").unwrap());
+    let isopath = Path::new(args.value_of("iso").unwrap());
+    let name = match args.value_of("name") {
+        Some(n) => String::from(n),
+        None => {
+            let isoname = isopath.file_stem().unwrap();
+            String::from(isoname.to_str().unwrap())
+        }
+    };
+
+    match install_game(path, isopath, name) {
+        Ok(()) => (),
+        Err(_) => println!("Could not install the game"),
+    }
+}
+
+fn list(args: &ArgMatches) {
+    let path = Path::new(args.value_of("image").unwrap());
+    let result = HddImage::open_read_only(path).and_then(|mut hdd| {
+        println!("APA disk image at {}", path.display());
+        hdd.list_games()
+    });
+
+    match result {
+        Ok(()) => (),
+        Err(_) => println!("Could not read the disk image"),
+    }
+}
+
+fn extract(args: &ArgMatches) {
+    let path = Path::new(args.value_of("image").unwrap());
+    let target = Path::new(args.value_of("target").unwrap());
+    let index = match args.value_of("index").map(|i| i.parse::<usize>()) {
+        Some(Ok(i)) => Some(i),
+        Some(Err(_)) => {
+            println!("Game index must be a number");
+            return;
+        }
+        None => None,
+    };
+
+    match extract_game(
+        path,
+        target,
+        index,
+        args.value_of("name"),
+        args.is_present("ul"),
+    ) {
+        Ok(()) => (),
+        Err(_) => println!("Could not extract the game"),
+    }
+}
+
+pub fn hdd(args: &ArgMatches) {
+    match args.subcommand() {
+        ("init", Some(args)) => init(args),
+        ("install", Some(args)) => install(args),
+        ("list", Some(args)) => list(args),
+        ("extract", Some(args)) => extract(args),
+        _ => (),
+    }
+}
@@ -0,0 +1,124 @@
+use crate::commands::add::prepare_name;
+use crate::game::media::MediaType;
+use crate::game::opl;
+use crate::ul::Ulcfg;
+
+use std::fs::{metadata, read_dir};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+
+const OPL_MEDIA_DIRS: [(&str, MediaType); 2] = [("DVD", MediaType::Dvd), ("CD", MediaType::Cd)];
+
+struct OplImage {
+    path: PathBuf,
+    serial: String,
+    title: String,
+    media: MediaType,
+}
+
+fn find_images(oplroot: &Path) -> Result<Vec<OplImage>> {
+    let mut images = Vec::new();
+    let dirs = OPL_MEDIA_DIRS
+        .iter()
+        .map(|(dir, media)| (oplroot.join(dir), *media))
+        .filter(|(path, _)| path.is_dir())
+        .collect::<Vec<_>>();
+
+    if dirs.is_empty() {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    for (path, media) in dirs {
+        let mut names = Vec::new();
+        for entry in read_dir(&path)? {
+            match entry.map(|e| e.file_name().into_string()) {
+                Ok(Ok(name)) => names.push(name),
+                Ok(Err(name)) => {
+                    println!(
+                        "Skipping {}: name is not valid UTF-8",
+                        name.to_string_lossy()
+                    )
+                }
+                Err(_) => println!("Skipping entry of {}: cannot be read", path.display()),
+            }
+        }
+        names.sort();
+
+        for name in names {
+            match opl::parse_iso_name(&name) {
+                Some((serial, title)) => images.push(OplImage {
+                    path: path.join(&name),
+                    serial,
+                    title,
+                    media,
+                }),
+                None => println!("Skipping {}: not named like SERIAL.Title.iso", name),
+            }
+        }
+    }
+
+    Ok(images)
+}
+
+fn import_games(oplroot: &Path, dstpath: &Path) -> Result<()> {
+    let ulpath = dstpath.join(Path::new("ul.cfg"));
+    let mut ulcfg = match ulpath.exists() {
+        true => Ulcfg::load(&ulpath)?,
+        false => Ulcfg::new(),
+    };
+
+    let mut pending = Vec::new();
+    for mut image in find_images(oplroot)? {
+        // Names too long or with characters OPL cannot show are fixed up as by add.
+        image.title = match prepare_name(image.title, false) {
+            Ok(title) => title,
+            Err(_) => continue,
+        };
+
+        if ulcfg.find_game(&image.title).is_some() {
+            println!("Skipping {}: already in ul.cfg", image.title);
+        } else {
+            pending.push(image);
+        }
+    }
+
+    let dstspace = fs2::available_space(dstpath)?;
+    let total = pending
+        .iter()
+        .map(|i| metadata(&i.path).map(|m| m.len()))
+        .sum::<Result<u64>>()?;
+
+    if total >= dstspace {
+        return Err(Error::from(ErrorKind::OutOfMemory));
+    }
+
+    let mut failed = 0;
+    for image in pending {
+        println!(
+            "Importing {} ({}) as {}",
+            image.title, image.serial, image.media
+        );
+        match ulcfg.import_game(&image.path, dstpath, image.title, image.media) {
+            // Saved after every game, so interrupted import keeps what is done.
+            Ok(()) => ulcfg.save(&ulpath)?,
+            Err(_) => failed += 1,
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::from(ErrorKind::Other)),
+    }
+}
+
+pub fn import(args: &ArgMatches) {
+    let oplroot = Path::new(args.value_of("oplroot").unwrap());
+    let dstpath = Path::new(args.value_of("ulpath").unwrap());
+
+    match import_games(oplroot, dstpath) {
+        Ok(()) => (),
+        Err(_) => println!("Could not import all games"),
+    }
+}
@@ -0,0 +1,137 @@
+use crate::game::iso::{read_layers, read_volume_descriptor};
+use crate::game::media::MediaType;
+use crate::game::name::region_from_serial;
+use crate::game::source::chunked::ChunkedImage;
+use crate::game::source::{with_image_start, ImageSource};
+use crate::game::systemcnf::SystemCnf;
+use crate::ul::Ulcfg;
+
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+const UNKNOWN: &str = "unknown";
+
+fn print_field(key: &str, value: &str) {
+    println!("{:<16}{}", format!("{}:", key), value);
+}
+
+/// Tells number of layers and where second one starts.
+fn describe_layers<T: Read + Seek>(image: T, size: u64) -> String {
+    match read_layers(image, size) {
+        Ok(Some(layers)) => match layers.layer_break {
+            Some(sector) => format!("2, layer break at sector {}", sector),
+            None => String::from("2, second layer descriptor is missing"),
+        },
+        Ok(None) => String::from("1"),
+        Err(_) => String::from(UNKNOWN),
+    }
+}
+
+/// Prints what `SYSTEM.CNF` and volume descriptors of `image` tell.
+///
+/// Layers are only looked for when whole image of `size` bytes can be read.
+fn print_disc<T: Read + Seek>(mut image: T, media: MediaType, size: Option<u64>) -> Result<()> {
+    let cnf = SystemCnf::read(&mut image).ok();
+    image.seek(SeekFrom::Start(0))?;
+    let pvd = read_volume_descriptor(&mut image).ok();
+    image.seek(SeekFrom::Start(0))?;
+    let layers = match (media, size) {
+        (MediaType::Dvd, Some(size)) => Some(describe_layers(&mut image, size)),
+        _ => None,
+    };
+
+    let serial = cnf.as_ref().and_then(|c| c.serial().ok());
+    let region = serial.as_deref().and_then(region_from_serial);
+    let video_mode = cnf.as_ref().and_then(|c| c.video_mode);
+
+    print_field("Serial", serial.as_deref().unwrap_or(UNKNOWN));
+    print_field("Media", &media.to_string());
+    print_field("Region", region.unwrap_or(UNKNOWN));
+    print_field(
+        "Video mode",
+        &video_mode.map_or(String::from(UNKNOWN), |m| m.to_string()),
+    );
+    print_field(
+        "Volume label",
+        pvd.as_ref().map_or(UNKNOWN, |p| p.label.as_str()),
+    );
+    print_field(
+        "Created",
+        pvd.as_ref()
+            .and_then(|p| p.created.as_deref())
+            .unwrap_or(UNKNOWN),
+    );
+    if let Some(layers) = layers {
+        print_field("Layers", &layers);
+    }
+    print_field("Boot ELF", cnf.as_ref().map_or(UNKNOWN, |c| c.boot_file()));
+
+    Ok(())
+}
+
+fn game_info(path: &Path, game: &str) -> Result<()> {
+    let ulpath = path.join(Path::new("ul.cfg"));
+    let ulcfg = Ulcfg::load(&ulpath)?;
+    let game = ulcfg
+        .select_game(game)
+        .and_then(|i| ulcfg.game(i))
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+    let chunks = game.chunk_files();
+    print_field("Name", &game.opl_name);
+    print_field("CRC name", game.crc_name());
+    print_field("Stored serial", &game.serial());
+    print_field("Size", &format!("{} bytes", game.size()));
+
+    match chunks.is_empty() {
+        false => {
+            let paths = chunks.iter().map(|(p, _)| *p).collect::<Vec<&Path>>();
+            let image = ChunkedImage::open(&paths)?;
+            let size = image.size();
+            print_disc(image, game.media(), Some(size))?;
+        }
+        true => println!("No chunks found for this game"),
+    }
+
+    println!("Chunks:");
+    for (chunk, size) in chunks {
+        let name = chunk.file_name().unwrap().to_string_lossy();
+        println!("  {:<32}{} bytes", name, size);
+    }
+
+    Ok(())
+}
+
+fn image_info(path: &Path) -> Result<()> {
+    print_field("Image", &path.display().to_string());
+
+    with_image_start(path, |start| {
+        if let Some(size) = start.size {
+            print_field("Size", &format!("{} bytes", size));
+        }
+        // Only start of archived image is kept, so layers cannot be told.
+        let size = start.size.filter(|_| start.whole);
+        print_disc(start.image, start.media, size)
+    })
+}
+
+pub fn info(args: &ArgMatches) {
+    let path = Path::new(args.value_of("path").unwrap());
+
+    let result = match args.value_of("game") {
+        Some(game) => game_info(path, game),
+        None if path.is_file() => image_info(path),
+        None => {
+            println!("Game has to be given when path is directory with ul.cfg");
+            return;
+        }
+    };
+
+    match result {
+        Ok(()) => (),
+        Err(_) => println!("Could not show game info"),
+    }
+}
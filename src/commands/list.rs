@@ -1,3 +1,6 @@
+use crate::game::config::GameConfig;
+use crate::game::titles::TitleDb;
+use crate::ul::record::{to_delimited, to_json, ListFormat};
 use crate::ul::Ulcfg;
 
 use fs2;
@@ -8,16 +11,64 @@ use std::path::{Path, PathBuf};
 
 use clap::ArgMatches;
 
-fn list_games(path: &Path) -> Result<()> {
+fn list_configs(path: &Path, ulcfg: &Ulcfg) {
+    for index in 0..ulcfg.num_games() {
+        let serial = match ulcfg.game_serial(index) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+
+        // One unreadable file should not hide settings of other games.
+        let config = GameConfig::load(&GameConfig::path(path, &serial));
+        if config.as_ref().is_ok_and(|c| c.is_empty()) {
+            continue;
+        }
+
+        println!(
+            "{}: {} ({})",
+            index,
+            ulcfg.game_name(index).unwrap(),
+            serial
+        );
+        match config {
+            Ok(config) => {
+                for line in config.describe() {
+                    println!("  {}", line);
+                }
+            }
+            Err(_) => println!("  Could not read the game configuration"),
+        }
+    }
+}
+
+/// Prints games alone in format meant for scripts.
+fn print_records(path: &Path, format: ListFormat) -> Result<()> {
+    let ulpath = path.join(Path::new("ul.cfg"));
+    let records = Ulcfg::load(&ulpath)?.records();
+
+    match format {
+        ListFormat::Json => println!("{}", to_json(&records)),
+        _ => println!("{}", to_delimited(&records, format)),
+    }
+
+    Ok(())
+}
+
+fn list_games(path: &Path, verbose: bool, titles: Option<&Path>) -> Result<()> {
     let ulpath = path.join(Path::new("ul.cfg"));
     let ulcfg = Ulcfg::load(&ulpath)?;
+    let titles = titles.map(TitleDb::load).transpose()?;
 
     let free = fs2::available_space(&path)? as f64;
     let realpath = canonicalize(&path)?;
 
     println!("ul.cfg at {}", realpath.display());
     println!("Available space: {:.2}GB", free / 1000000000.0);
-    ulcfg.list_games();
+    ulcfg.list_games(path, titles.as_ref());
+
+    if verbose {
+        list_configs(path, &ulcfg);
+    }
 
     Ok(())
 }
@@ -28,7 +79,15 @@ pub fn list(args: &ArgMatches) {
         None => current_dir().unwrap(),
     };
 
-    match list_games(path.as_path()) {
+    let format = args.value_of("format").unwrap().parse().unwrap();
+    let titles = args.value_of("titles").map(Path::new);
+
+    let result = match format {
+        ListFormat::Table => list_games(path.as_path(), args.is_present("verbose"), titles),
+        _ => print_records(path.as_path(), format),
+    };
+
+    match result {
         Ok(()) => (),
         Err(_) => println!("Could not load ul.cfg"),
     };
@@ -1,3 +1,12 @@
 pub mod add;
+pub mod apps;
+pub mod art;
+pub mod cheats;
+pub mod config;
 pub mod delete;
+pub mod export;
+pub mod hdd;
+pub mod import;
+pub mod info;
 pub mod list;
+pub mod vmc;
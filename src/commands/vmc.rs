@@ -0,0 +1,217 @@
+use crate::game::config::GameConfig;
+use crate::ul::table;
+use crate::ul::Ulcfg;
+use crate::vmc::save::SaveFolder;
+use crate::vmc::VmcImage;
+
+use std::fs::create_dir_all;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+
+// OPL looks for cards in VMC/<name>.bin next to ul.cfg
+const VMC_DIR: &str = "VMC";
+const VMC_NAME_SIZE: usize = 32;
+
+fn check_vmc_name(name: &str) -> Result<()> {
+    let invalid = |c: char| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+    if name.is_empty() || name.len() > VMC_NAME_SIZE || name.contains(invalid) {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    Ok(())
+}
+
+fn vmc_path(ulpath: &Path, name: &str) -> PathBuf {
+    ulpath.join(VMC_DIR).join(format!("{}.bin", name))
+}
+
+fn link_card(ulpath: &Path, game: &str, slot: usize, name: &str) -> Result<()> {
+    let ulcfg = Ulcfg::load(&ulpath.join(Path::new("ul.cfg")))?;
+    let index = ulcfg
+        .select_game(game)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+    let cfgpath = GameConfig::path(ulpath, &ulcfg.game_serial(index)?);
+    let mut config = GameConfig::load(&cfgpath)?;
+    config.set_vmc(slot, name)?;
+    config.save(&cfgpath)?;
+
+    println!(
+        "Linked {} to slot{} of {}",
+        name,
+        slot,
+        ulcfg.game_name(index).unwrap()
+    );
+    Ok(())
+}
+
+fn create_card(
+    ulpath: &Path,
+    name: &str,
+    size_mb: u64,
+    ecc: bool,
+    link: Option<(&str, usize)>,
+) -> Result<()> {
+    check_vmc_name(name)?;
+    let path = vmc_path(ulpath, name);
+    if path.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    create_dir_all(path.parent().unwrap())?;
+    let card = VmcImage::format(size_mb, ecc)?;
+    card.save(&path)?;
+    println!("Created {}MB memory card {}", size_mb, path.display());
+
+    match link {
+        Some((game, slot)) => link_card(ulpath, game, slot, name),
+        None => Ok(()),
+    }
+}
+
+fn open_card(ulpath: &Path, name: &str) -> Result<(PathBuf, VmcImage)> {
+    check_vmc_name(name)?;
+    let path = vmc_path(ulpath, name);
+    let card = VmcImage::open(&path)?;
+    Ok((path, card))
+}
+
+fn list_saves(ulpath: &Path, name: &str) -> Result<()> {
+    let (path, card) = open_card(ulpath, name)?;
+    let rows = card
+        .saves()?
+        .iter()
+        .map(|save| {
+            vec![
+                String::from(&save.entry.name),
+                save.files.len().to_string(),
+                format!("{}KB", save.size().div_ceil(1024)),
+            ]
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    // Names read from card may hold multi-byte characters.
+    let name_size = rows.iter().map(|r| r[0].len()).fold(32, usize::max);
+    let col_names = ["Name", "Files", "Size"];
+    let col_sizes = vec![name_size, 5, 8];
+    let hline = table::make_hline(&col_sizes);
+
+    println!("{}", path.display());
+    println!("Free space: {}KB", card.free_space() / 1024);
+    println!("{}", hline);
+    println!(
+        "{}",
+        table::make_row(
+            col_names.iter().map(|c| c.to_string()).collect(),
+            &col_sizes
+        )
+    );
+    println!("{}", hline);
+
+    for row in rows {
+        println!("{}", table::make_row(row, &col_sizes));
+    }
+
+    println!("{}", hline);
+    Ok(())
+}
+
+fn import_save(ulpath: &Path, name: &str, savepath: &Path) -> Result<()> {
+    let (path, mut card) = open_card(ulpath, name)?;
+    let save = SaveFolder::load(savepath)?;
+
+    println!("Importing {} into {}", save.entry.name, name);
+    card.add_save(&save)?;
+    card.save(&path)
+}
+
+fn export_save(ulpath: &Path, name: &str, folder: &str, target: &Path) -> Result<()> {
+    let (_, card) = open_card(ulpath, name)?;
+    if target.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    let save = card
+        .saves()?
+        .into_iter()
+        .find(|s| s.entry.name == folder)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+    println!("Exporting {} to {}", folder, target.display());
+    save.save(target)
+}
+
+fn create(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let name = args.value_of("name").unwrap();
+    // Both are limited to valid values by clap.
+    let size = args.value_of("size").unwrap().parse::<u64>().unwrap();
+    let slot = args.value_of("slot").unwrap().parse::<usize>().unwrap();
+
+    let link = args.value_of("game").map(|g| (g, slot));
+    match create_card(ulpath, name, size, args.is_present("ecc"), link) {
+        Ok(()) => (),
+        Err(_) => println!("Could not create the memory card"),
+    }
+}
+
+fn ls(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let name = args.value_of("name").unwrap();
+
+    match list_saves(ulpath, name) {
+        Ok(()) => (),
+        Err(_) => println!("Could not read the memory card"),
+    }
+}
+
+fn import(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let name = args.value_of("name").unwrap();
+    let savepath = Path::new(args.value_of("save").unwrap());
+
+    match import_save(ulpath, name, savepath) {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            println!("Memory card already holds save folder with the same name")
+        }
+        Err(_) => println!("Could not import the save"),
+    }
+}
+
+fn export(args: &ArgMatches) {
+    let ulpath = Path::new(args.value_of("ulpath").unwrap());
+    let name = args.value_of("name").unwrap();
+    let folder = args.value_of("folder").unwrap();
+    let target = Path::new(args.value_of("target").unwrap());
+
+    match export_save(ulpath, name, folder, target) {
+        Ok(()) => (),
+        Err(_) => println!("Could not export the save"),
+    }
+}
+
+pub fn vmc(args: &ArgMatches) {
+    match args.subcommand() {
+        ("create", Some(args)) => create(args),
+        ("export", Some(args)) => export(args),
+        ("import", Some(args)) => import(args),
+        ("ls", Some(args)) => ls(args),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_vmc_name() {
+        assert!(check_vmc_name("SLUS_123.45").is_ok());
+        assert!(check_vmc_name("").is_err());
+        assert!(check_vmc_name("../foo").is_err());
+        assert!(check_vmc_name(&"a".repeat(33)).is_err());
+    }
+}
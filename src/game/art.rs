@@ -0,0 +1,195 @@
+use std::fs::{create_dir_all, read_dir};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::ImageFormat;
+
+// OPL looks for art in ART/<SERIAL>_<KIND>.jpg or .png next to ul.cfg
+const ART_DIR: &str = "ART";
+const ART_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Kind of OPL art file, with its suffix, size and format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArtKind {
+    pub suffix: &'static str,
+    /// Letter shown in `list` when art is present.
+    pub flag: char,
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+}
+
+pub const ART_KINDS: [ArtKind; 8] = [
+    art("COV", 'C', 140, 200, ImageFormat::Jpeg),
+    art("COV2", '2', 242, 344, ImageFormat::Jpeg),
+    art("ICO", 'I', 64, 64, ImageFormat::Png),
+    art("LAB", 'L', 18, 240, ImageFormat::Jpeg),
+    art("LGO", 'G', 300, 125, ImageFormat::Png),
+    art("BG", 'B', 640, 480, ImageFormat::Jpeg),
+    art("SCR", 'S', 250, 188, ImageFormat::Jpeg),
+    art("SCR2", 'T', 250, 188, ImageFormat::Jpeg),
+];
+
+const fn art(
+    suffix: &'static str,
+    flag: char,
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+) -> ArtKind {
+    ArtKind {
+        suffix,
+        flag,
+        width,
+        height,
+        format,
+    }
+}
+
+impl ArtKind {
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        ART_KINDS
+            .iter()
+            .find(|k| k.suffix.eq_ignore_ascii_case(suffix))
+            .copied()
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            ImageFormat::Png => "png",
+            _ => "jpg",
+        }
+    }
+
+    /// Path of art for game `serial` in `ulpath` directory.
+    pub fn path(&self, ulpath: &Path, serial: &str) -> PathBuf {
+        let name = format!("{}_{}.{}", serial, self.suffix, self.extension());
+        ulpath.join(ART_DIR).join(name)
+    }
+
+    /// Tells whether game `serial` has this art, in any format OPL reads.
+    pub fn exists(&self, ulpath: &Path, serial: &str) -> bool {
+        ["jpg", "png"].iter().any(|ext| {
+            let name = format!("{}_{}.{}", serial, self.suffix, ext);
+            ulpath.join(ART_DIR).join(name).exists()
+        })
+    }
+}
+
+/// Art file found in local directory, not yet matched to a game.
+pub struct ArtFile {
+    pub path: PathBuf,
+    /// File name without kind suffix, either serial or title of the game.
+    pub key: String,
+    pub kind: ArtKind,
+}
+
+/// Reduces serial or title to lowercase letters and digits, so
+/// `SLUS_123.45`, `SLUS-12345` and `slus12345` are the same key.
+pub fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Splits file stem like `SLUS_123.45_COV` into key and art kind.
+///
+/// Stems without known suffix are taken as front covers.
+fn parse_stem(stem: &str) -> (String, ArtKind) {
+    let cover = ART_KINDS[0];
+    match stem.rsplit_once('_') {
+        Some((key, suffix)) => match ArtKind::from_suffix(suffix) {
+            Some(kind) => (String::from(key), kind),
+            None => (String::from(stem), cover),
+        },
+        None => (String::from(stem), cover),
+    }
+}
+
+/// Art files in `dir`, sorted by name.
+pub fn find_art(dir: &Path) -> Result<Vec<ArtFile>> {
+    let mut paths = read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| ART_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+            let (key, kind) = parse_stem(&stem);
+            ArtFile { path, key, kind }
+        })
+        .collect();
+
+    Ok(files)
+}
+
+/// Converts image at `src` to size and format OPL expects for `kind`.
+pub fn convert_art(src: &Path, dst: &Path, kind: &ArtKind) -> Result<()> {
+    let image = image::open(src).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    let resized = image.resize_exact(kind.width, kind.height, FilterType::Lanczos3);
+
+    create_dir_all(dst.parent().unwrap())?;
+    // JPEG cannot hold alpha channel.
+    let result = match kind.format {
+        ImageFormat::Png => resized.to_rgba8().save_with_format(dst, kind.format),
+        _ => resized.to_rgb8().save_with_format(dst, kind.format),
+    };
+
+    result.map_err(|_| Error::from(ErrorKind::Other))
+}
+
+/// Flags of art present for game `serial`, with `-` in place of missing ones.
+pub fn art_flags(ulpath: &Path, serial: &str) -> String {
+    ART_KINDS
+        .iter()
+        .map(|k| match k.exists(ulpath, serial) {
+            true => k.flag,
+            false => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+
+    #[test]
+    fn test_normalize_key() {
+        assert_eq!(normalize_key("SLUS_123.45"), "slus12345");
+        assert_eq!(normalize_key("SLUS-12345"), normalize_key("slus12345"));
+        assert_eq!(normalize_key("Foo: Bar"), "foobar");
+    }
+
+    #[test]
+    fn test_parse_stem() {
+        let (key, kind) = parse_stem("SLUS_123.45_ICO");
+        assert_eq!((key.as_str(), kind.suffix), ("SLUS_123.45", "ICO"));
+        let (key, kind) = parse_stem("Foo_Bar");
+        assert_eq!((key.as_str(), kind.suffix), ("Foo_Bar", "COV"));
+        let (_, kind) = parse_stem("Foo_cov2");
+        assert_eq!(kind.suffix, "COV2");
+    }
+
+    #[test]
+    fn test_convert_art() {
+        let dir = TestDir::new("art");
+        let src = dir.join("src.png");
+        let dst = dir.join("ART").join("SLUS_123.45_COV.jpg");
+        image::RgbaImage::new(300, 400).save(&src).unwrap();
+
+        convert_art(&src, &dst, &ART_KINDS[0]).unwrap();
+        let converted = image::open(&dst).unwrap();
+        assert_eq!((converted.width(), converted.height()), (140, 200));
+    }
+}
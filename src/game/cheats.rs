@@ -0,0 +1,280 @@
+use crate::game::art::normalize_key;
+
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+// OPL reads cheats from CHT/<serial>.cht next to ul.cfg
+const CHT_DIR: &str = "CHT";
+const CHT_COMMENT: &str = "//";
+// PS2 Cheat Code databases put serial into title, e.g. "Title /ID SLUS_200.62"
+const CHT_ID: &str = "/ID";
+// CodeBreaker v7 and later start encrypted code lists with this code,
+// which OPL cannot decrypt.
+const CB_V7_HEADER: &str = "BEEFC0D";
+
+/// Named group of codes, enabled or disabled as a whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheatGroup {
+    pub name: String,
+    pub codes: Vec<String>,
+    pub enabled: bool,
+}
+
+/// Cheats of single game, as stored in OPL `.cht` file.
+///
+/// Disabled groups are kept commented out, so OPL skips them.
+#[derive(Debug, PartialEq)]
+pub struct CheatFile {
+    pub title: String,
+    pub serial: Option<String>,
+    pub groups: Vec<CheatGroup>,
+}
+
+/// Tells whether line is raw code like `2028A1B0 0000000A`.
+fn is_code(line: &str) -> bool {
+    let parts = line.split_whitespace().collect::<Vec<&str>>();
+    parts.len() == 2
+        && parts
+            .iter()
+            .all(|p| p.len() == 8 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn format_code(line: &str) -> String {
+    line.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_ascii_uppercase()
+}
+
+/// Splits quoted game header into title and optional serial.
+fn parse_header(line: &str) -> (String, Option<String>) {
+    let header = line.trim_matches('"').trim();
+    match header.split_once(CHT_ID) {
+        Some((title, serial)) => (
+            String::from(title.trim()),
+            Some(String::from(serial.trim())),
+        ),
+        None => (String::from(header), None),
+    }
+}
+
+/// Parses every game found in cheat list.
+///
+/// Accepts PS2 Cheat Code and CodeBreaker text formats: quoted game title,
+/// then cheat names, each followed by its codes. Commented codes make
+/// disabled group, other comments are dropped.
+pub fn parse_cheats(contents: &str) -> Vec<CheatFile> {
+    let mut games: Vec<CheatFile> = Vec::new();
+
+    for line in contents.lines().map(|l| l.trim()) {
+        let (line, enabled) = match line.strip_prefix(CHT_COMMENT) {
+            Some(l) => (l.trim(), false),
+            None => (line, true),
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('"') && enabled {
+            let (title, serial) = parse_header(line);
+            games.push(CheatFile {
+                title,
+                serial,
+                groups: Vec::new(),
+            });
+            continue;
+        }
+
+        let game = match games.last_mut() {
+            Some(g) => g,
+            None => continue,
+        };
+
+        if !is_code(line) {
+            game.groups.push(CheatGroup {
+                name: String::from(line),
+                codes: Vec::new(),
+                enabled,
+            });
+            continue;
+        }
+
+        // Plain comment followed by active codes was not a group name.
+        if let Some(group) = game.groups.last() {
+            if enabled && !group.enabled && group.codes.is_empty() {
+                game.groups.pop();
+            }
+        }
+
+        if let Some(group) = game.groups.last_mut() {
+            // First code decides, as database may comment out codes only.
+            if group.codes.is_empty() {
+                group.enabled = enabled;
+            }
+            group.codes.push(format_code(line));
+        }
+    }
+
+    for game in games.iter_mut() {
+        game.groups.retain(|g| !g.codes.is_empty());
+    }
+
+    games
+}
+
+/// Finds cheats of game in database, by serial or else by exact title.
+pub fn find_cheats(contents: &str, serial: &str, name: &str) -> Option<CheatFile> {
+    let games = parse_cheats(contents);
+    let by_serial = games.iter().position(|g| {
+        g.serial
+            .as_ref()
+            .is_some_and(|s| normalize_key(s) == normalize_key(serial))
+    });
+    let by_name = || {
+        games
+            .iter()
+            .position(|g| normalize_key(&g.title) == normalize_key(name))
+    };
+
+    let index = by_serial.or_else(by_name)?;
+    games.into_iter().nth(index)
+}
+
+impl CheatFile {
+    /// Path of cheat file for game `serial` in `ulpath` directory.
+    pub fn path(ulpath: &Path, serial: &str) -> PathBuf {
+        ulpath.join(CHT_DIR).join(format!("{}.cht", serial))
+    }
+
+    /// Tells whether codes are CodeBreaker v7 encrypted, by their header code.
+    pub fn is_encrypted(&self) -> bool {
+        self.groups
+            .iter()
+            .flat_map(|g| g.codes.iter())
+            .any(|c| c.starts_with(CB_V7_HEADER))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        parse_cheats(&read_to_string(path)?)
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        write(path, self.to_string())
+    }
+
+    /// Finds group by its number, counted from 1, or by name.
+    pub fn select_group(&self, group: &str) -> Option<usize> {
+        if let Ok(number) = group.parse::<usize>() {
+            if number > 0 && number <= self.groups.len() {
+                return Some(number - 1);
+            }
+        }
+
+        self.groups
+            .iter()
+            .position(|g| g.name.eq_ignore_ascii_case(group))
+    }
+}
+
+impl std::fmt::Display for CheatFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.serial {
+            Some(serial) => writeln!(f, "\"{} {} {}\"", self.title, CHT_ID, serial)?,
+            None => writeln!(f, "\"{}\"", self.title)?,
+        }
+
+        for group in &self.groups {
+            let prefix = match group.enabled {
+                true => "",
+                false => "// ",
+            };
+
+            writeln!(f)?;
+            writeln!(f, "{}{}", prefix, group.name)?;
+            for code in &group.codes {
+                writeln!(f, "{}{}", prefix, code)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = concat!(
+        "// Sample database\n",
+        "\"Foo /ID SLUS_123.45\"\n",
+        "Mastercode\n",
+        "902d5f28 0c0b95c6\n",
+        "\n",
+        "Infinite Health\n",
+        "// 2028A1B0 0000000A\n",
+        "\"Bar\"\n",
+        "Max Money\n",
+        "// checked on PAL\n",
+        "203F0000 0098967F\n",
+    );
+
+    #[test]
+    fn test_parse_cheats() {
+        let games = parse_cheats(DATABASE);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].title, "Foo");
+        assert_eq!(games[0].serial.as_deref(), Some("SLUS_123.45"));
+        assert_eq!(games[0].groups[0].codes, vec!["902D5F28 0C0B95C6"]);
+        assert!(!games[0].groups[1].enabled);
+        assert_eq!(games[1].groups.len(), 1);
+        assert!(games[1].groups[0].enabled);
+    }
+
+    #[test]
+    fn test_find_cheats() {
+        let foo = find_cheats(DATABASE, "SLUS-12345", "Whatever").unwrap();
+        assert_eq!(foo.title, "Foo");
+        let bar = find_cheats(DATABASE, "SLES_999.99", "bar").unwrap();
+        assert_eq!(bar.groups[0].name, "Max Money");
+        assert!(find_cheats(DATABASE, "SLES_999.99", "Baz").is_none());
+    }
+
+    #[test]
+    fn test_encrypted_cheats() {
+        let contents = concat!(
+            "\"Baz /ID SLES_500.00\"\n",
+            "Enable Code\n",
+            "beefc0de 00000000\n",
+            "Infinite Ammo\n",
+            "2A97E4B1 1BB3A7F3\n",
+        );
+        assert!(find_cheats(contents, "SLES_500.00", "")
+            .unwrap()
+            .is_encrypted());
+        assert!(!find_cheats(DATABASE, "SLUS_123.45", "")
+            .unwrap()
+            .is_encrypted());
+    }
+
+    #[test]
+    fn test_cheat_file_roundtrip() {
+        let mut cheats = find_cheats(DATABASE, "SLUS_123.45", "").unwrap();
+        cheats.groups[1].enabled = true;
+        assert_eq!(cheats.select_group("infinite health"), Some(1));
+        assert_eq!(cheats.select_group("1"), Some(0));
+        assert_eq!(cheats.select_group("3"), None);
+
+        let contents = cheats.to_string();
+        assert!(contents.starts_with("\"Foo /ID SLUS_123.45\"\n\nMastercode\n"));
+        assert_eq!(parse_cheats(&contents), vec![cheats]);
+    }
+}
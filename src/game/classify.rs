@@ -0,0 +1,101 @@
+use crate::game::iso::read_volume_label;
+use crate::game::media::MediaType;
+use crate::game::systemcnf::SystemCnf;
+
+use std::io::prelude::*;
+use std::io::{Result, SeekFrom};
+
+use iso9660::{DirectoryEntry, ISO9660};
+
+const VIDEO_TS_PATH: &str = "/VIDEO_TS";
+
+/// What disc image holds, as far as OPL is concerned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageKind {
+    Ps2Dvd,
+    Ps2Cd,
+    Ps1,
+    DvdVideo,
+    Unknown,
+}
+
+impl std::fmt::Display for ImageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImageKind::Ps2Dvd => write!(f, "PS2 DVD"),
+            ImageKind::Ps2Cd => write!(f, "PS2 CD"),
+            ImageKind::Ps1 => write!(f, "PS1"),
+            ImageKind::DvdVideo => write!(f, "DVD-Video"),
+            ImageKind::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Tells kind of disc from its `SYSTEM.CNF` and whether it has `VIDEO_TS`.
+fn kind_of(cnf: Option<SystemCnf>, video_ts: bool, media: MediaType) -> ImageKind {
+    match (cnf, media) {
+        (Some(cnf), _) if cnf.boot2.is_none() => ImageKind::Ps1,
+        (Some(_), MediaType::Cd) => ImageKind::Ps2Cd,
+        (Some(_), MediaType::Dvd) => ImageKind::Ps2Dvd,
+        (None, _) if video_ts => ImageKind::DvdVideo,
+        (None, _) => ImageKind::Unknown,
+    }
+}
+
+fn has_video_ts<T: Read + Seek>(image: T) -> bool {
+    let entry = ISO9660::new(image)
+        .ok()
+        .and_then(|iso| iso.open(VIDEO_TS_PATH).ok());
+    matches!(entry, Some(Some(DirectoryEntry::Directory(_))))
+}
+
+/// Classifies ISO9660 `image` dumped from `media`.
+pub fn classify<T: Read + Seek>(mut image: T, media: MediaType) -> Result<ImageKind> {
+    // Without primary volume descriptor, this is no disc image at all.
+    if read_volume_label(&mut image).is_err() {
+        return Ok(ImageKind::Unknown);
+    }
+
+    image.seek(SeekFrom::Start(0))?;
+    let cnf = SystemCnf::read(&mut image).ok();
+
+    image.seek(SeekFrom::Start(0))?;
+    let video_ts = cnf.is_none() && has_video_ts(&mut image);
+
+    Ok(kind_of(cnf, video_ts, media))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::source::with_image_start;
+    use std::path::{Path, PathBuf};
+
+    fn classify_image(path: &Path) -> Result<ImageKind> {
+        with_image_start(path, |s| classify(s.image, s.media))
+    }
+
+    #[test]
+    fn test_classify_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        assert_eq!(classify_image(&path).unwrap(), ImageKind::Ps2Dvd);
+
+        path.set_extension("cue");
+        assert_eq!(classify_image(&path).unwrap(), ImageKind::Ps2Cd);
+
+        path.set_file_name("testimage.7z");
+        assert_eq!(classify_image(&path).unwrap(), ImageKind::Ps2Dvd);
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert_eq!(classify_image(&path).unwrap(), ImageKind::Unknown);
+    }
+
+    #[test]
+    fn test_kind_of() {
+        let ps1 = SystemCnf::parse("BOOT = cdrom:\\SLUS_005.94;1\n").unwrap();
+        assert_eq!(kind_of(Some(ps1), false, MediaType::Cd), ImageKind::Ps1);
+        assert_eq!(kind_of(None, true, MediaType::Dvd), ImageKind::DvdVideo);
+        assert_eq!(kind_of(None, false, MediaType::Dvd), ImageKind::Unknown);
+    }
+}
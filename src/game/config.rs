@@ -0,0 +1,243 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+// OPL keeps per-game settings in CFG/<serial>.cfg next to ul.cfg
+const CFG_DIR: &str = "CFG";
+const CFG_COMPATIBILITY: &str = "$Compatibility";
+const CFG_CONFIG_SOURCE: &str = "$ConfigSource";
+const CFG_VMC_PREFIX: &str = "$VMC_";
+const CFG_ENABLE_CHEAT: &str = "$EnableCheat";
+// Settings edited through ulmake are marked as user made, like OPL does.
+const CFG_SOURCE_USER: &str = "1";
+const CFG_MAX_MODE: u8 = 8;
+pub const CFG_VMC_SLOTS: usize = 2;
+
+/// OPL per-game configuration, kept as ordered `key=value` pairs.
+///
+/// Keys ulmake does not know about are preserved as read.
+pub struct GameConfig {
+    entries: Vec<(String, String)>,
+}
+
+impl GameConfig {
+    /// Path of configuration file for game `serial` in `ulpath` directory.
+    pub fn path(ulpath: &Path, serial: &str) -> PathBuf {
+        ulpath.join(CFG_DIR).join(format!("{}.cfg", serial))
+    }
+
+    /// Loads configuration, missing file being an empty one.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(GameConfig {
+                entries: Vec::new(),
+            });
+        }
+
+        Ok(GameConfig::parse(&read_to_string(path)?))
+    }
+
+    /// Parses `key=value` lines, skipping malformed ones like OPL does.
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (String::from(k), String::from(v.trim_end())))
+            .collect();
+
+        GameConfig { entries }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        write(path, self.to_string())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets `key` to `value`, dropping it altogether if `value` is empty.
+    pub fn set(&mut self, key: &str, value: &str) {
+        if value.is_empty() {
+            self.entries.retain(|(k, _)| k != key);
+            return;
+        }
+
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = String::from(value),
+            None => self.entries.push((String::from(key), String::from(value))),
+        }
+
+        if key != CFG_CONFIG_SOURCE {
+            self.set(CFG_CONFIG_SOURCE, CFG_SOURCE_USER);
+        }
+    }
+
+    /// Enabled compatibility modes, counted from 1 like in OPL menu.
+    pub fn modes(&self) -> Vec<u8> {
+        let mask = self
+            .get(CFG_COMPATIBILITY)
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(0);
+
+        (1..=CFG_MAX_MODE)
+            .filter(|m| mask & (1 << (m - 1)) != 0)
+            .collect()
+    }
+
+    pub fn set_modes(&mut self, modes: &[u8]) -> Result<()> {
+        if modes.iter().any(|m| *m == 0 || *m > CFG_MAX_MODE) {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        let mask = modes.iter().fold(0u32, |acc, m| acc | 1 << (m - 1));
+        match mask {
+            0 => self.set(CFG_COMPATIBILITY, ""),
+            m => self.set(CFG_COMPATIBILITY, &m.to_string()),
+        }
+
+        Ok(())
+    }
+
+    /// Name of virtual memory card assigned to `slot`.
+    pub fn vmc(&self, slot: usize) -> Option<&str> {
+        self.get(&format!("{}{}", CFG_VMC_PREFIX, slot))
+    }
+
+    pub fn set_vmc(&mut self, slot: usize, name: &str) -> Result<()> {
+        if slot >= CFG_VMC_SLOTS {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        self.set(&format!("{}{}", CFG_VMC_PREFIX, slot), name);
+        Ok(())
+    }
+
+    /// Turns on OPL cheat engine, which reads codes from CHT/ folder.
+    pub fn set_cheats(&mut self, enabled: bool) {
+        match enabled {
+            true => self.set(CFG_ENABLE_CHEAT, "1"),
+            false => self.set(CFG_ENABLE_CHEAT, ""),
+        }
+    }
+
+    /// Human readable settings, one per line.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let modes = self.modes();
+        if !modes.is_empty() {
+            let modes = modes.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+            lines.push(format!("Modes: {}", modes.join(",")));
+        }
+
+        for slot in 0..CFG_VMC_SLOTS {
+            if let Some(name) = self.vmc(slot) {
+                lines.push(format!("VMC slot{}: {}", slot, name));
+            }
+        }
+
+        let known = |k: &str| {
+            k == CFG_COMPATIBILITY || k == CFG_CONFIG_SOURCE || k.starts_with(CFG_VMC_PREFIX)
+        };
+
+        for (key, value) in self.entries.iter().filter(|(k, _)| !known(k)) {
+            lines.push(format!("{}: {}", key.trim_start_matches('$'), value));
+        }
+
+        lines
+    }
+}
+
+impl std::fmt::Display for GameConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (key, value) in &self.entries {
+            writeln!(f, "{}={}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses comma separated list of compatibility modes, e.g. `1,3`.
+pub fn parse_modes(modes: &str) -> Result<Vec<u8>> {
+    if modes == "none" {
+        return Ok(Vec::new());
+    }
+
+    modes
+        .split(',')
+        .map(|m| m.trim().parse::<u8>())
+        .collect::<std::result::Result<Vec<u8>, _>>()
+        .map_err(|_| Error::from(ErrorKind::InvalidInput))
+}
+
+/// Parses VMC assignment like `slot0=NAME`, empty name clearing the slot.
+pub fn parse_vmc(vmc: &str) -> Result<(usize, String)> {
+    let (slot, name) = vmc
+        .split_once('=')
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+    let slot = slot
+        .strip_prefix("slot")
+        .and_then(|s| s.parse::<usize>().ok())
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+
+    Ok((slot, String::from(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_roundtrip_keeps_unknown_keys() {
+        let contents = "$ConfigSource=1\n$EnableGSM=1\n$Compatibility=5\n";
+        let config = GameConfig::parse(contents);
+        assert_eq!(config.modes(), vec![1, 3]);
+        assert_eq!(config.to_string(), contents);
+    }
+
+    #[test]
+    fn test_config_skips_malformed_lines() {
+        let config = GameConfig::parse("$EnableGSM=1\ngarbage\n\n$Compatibility=1\n");
+        assert_eq!(config.modes(), vec![1]);
+        assert_eq!(config.to_string(), "$EnableGSM=1\n$Compatibility=1\n");
+    }
+
+    #[test]
+    fn test_config_set_modes_and_vmc() {
+        let mut config = GameConfig::parse("");
+        config.set_modes(&[2, 4]).unwrap();
+        config.set_vmc(0, "Foo").unwrap();
+        assert_eq!(config.get("$Compatibility"), Some("10"));
+        assert_eq!(config.vmc(0), Some("Foo"));
+        assert_eq!(config.get("$ConfigSource"), Some("1"));
+        assert!(config.set_modes(&[9]).is_err());
+        assert!(config.set_vmc(2, "Bar").is_err());
+        config.set_cheats(true);
+        assert_eq!(config.get("$EnableCheat"), Some("1"));
+        config.set_cheats(false);
+
+        config.set_modes(&[]).unwrap();
+        config.set_vmc(0, "").unwrap();
+        assert_eq!(config.to_string(), "$ConfigSource=1\n");
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(parse_modes("1,3").unwrap(), vec![1, 3]);
+        assert!(parse_modes("1,x").is_err());
+        assert_eq!(parse_vmc("slot1=Foo").unwrap(), (1, String::from("Foo")));
+        assert!(parse_vmc("Foo").is_err());
+    }
+}
@@ -1,13 +1,25 @@
+use crate::game::source;
+use crate::game::systemcnf::SystemCnf;
+
 use std::fs;
-use std::fs::File;
 use std::io::prelude::*;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, ErrorKind, Result, SeekFrom};
 use std::path::{Path, PathBuf};
 
-use iso9660::{DirectoryEntry, ISO9660};
-use regex::Regex;
-
-const SYSTEM_CNF_PATH: &str = "/SYSTEM.CNF";
+const ISO_SECTOR_SIZE: usize = 2048;
+// Primary volume descriptor follows 16 sectors of system area.
+const ISO_PVD_SECTOR: u64 = 16;
+const ISO_PVD_MAGIC: &[u8; 6] = b"\x01CD001";
+const ISO_VOLUME_ID: usize = 40;
+const ISO_VOLUME_ID_SIZE: usize = 32;
+// Volume space size is counted in logical blocks, both little endian.
+const ISO_VOLUME_SPACE_SIZE: usize = 80;
+const ISO_LOGICAL_BLOCK_SIZE: usize = 128;
+// Creation date is YYYYMMDDHHMMSScc in ASCII digits, then time zone byte.
+const ISO_CREATION_DATE: usize = 813;
+const ISO_DATE_SIZE: usize = 16;
+// Single layer DVD5 holds 2295104 sectors, anything larger has to be DVD9.
+const DVD5_SIZE: u64 = 4_700_372_992;
 const NUM_CHUNKNAME_SEGMENTS: usize = 5;
 
 pub struct ISOChunk {
@@ -18,12 +30,120 @@ pub struct GameChunk {
     path: PathBuf,
 }
 
+/// Fields of primary volume descriptor worth showing to user.
+pub struct VolumeDescriptor {
+    pub label: String,
+    /// Creation date as `YYYY-MM-DD HH:MM:SS`, if disc has one.
+    pub created: Option<String>,
+    /// Size of volume in bytes, which image should not be shorter than.
+    pub size: u64,
+}
+
+/// Layout of dual-layer DVD9 image.
+///
+/// PS2 discs keep layers as separate volumes. Second layer volume descriptor
+/// sits at sector equal to first layer volume size, so layer break, counted
+/// as OPL expects it, is that volume size less 16 sectors of system area.
+pub struct Layers {
+    /// Layer break in sectors, unknown if second descriptor is missing.
+    pub layer_break: Option<u64>,
+}
+
 pub trait Chunk {
     fn serial(&self) -> Result<String>;
     fn size(&self) -> Result<u64>;
     fn path(&self) -> &Path;
 }
 
+/// Reads game serial from `SYSTEM.CNF` of ISO9660 `image`.
+pub fn read_serial<T: Read + Seek>(image: T) -> Result<String> {
+    SystemCnf::read(image)?.serial()
+}
+
+/// Formats ISO9660 dec-datetime, which is all zeros when date is not set.
+fn parse_date(date: &[u8]) -> Option<String> {
+    let date = std::str::from_utf8(date).ok()?;
+    if !date.bytes().all(|b| b.is_ascii_digit()) || date.bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    Some(format!(
+        "{}-{}-{} {}:{}:{}",
+        &date[0..4],
+        &date[4..6],
+        &date[6..8],
+        &date[8..10],
+        &date[10..12],
+        &date[12..14]
+    ))
+}
+
+/// Reads primary volume descriptor of ISO9660 `image`.
+pub fn read_volume_descriptor<T: Read + Seek>(mut image: T) -> Result<VolumeDescriptor> {
+    let mut pvd = vec![0x00; ISO_SECTOR_SIZE];
+    image.seek(SeekFrom::Start(ISO_PVD_SECTOR * ISO_SECTOR_SIZE as u64))?;
+    image.read_exact(&mut pvd)?;
+
+    if !pvd.starts_with(ISO_PVD_MAGIC) {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+
+    let label = &pvd[ISO_VOLUME_ID..ISO_VOLUME_ID + ISO_VOLUME_ID_SIZE];
+    let label = String::from_utf8_lossy(label);
+
+    let blocks = &pvd[ISO_VOLUME_SPACE_SIZE..ISO_VOLUME_SPACE_SIZE + 4];
+    let blocks = u32::from_le_bytes(blocks.try_into().unwrap());
+    let block_size = &pvd[ISO_LOGICAL_BLOCK_SIZE..ISO_LOGICAL_BLOCK_SIZE + 2];
+    let block_size = u16::from_le_bytes(block_size.try_into().unwrap());
+
+    Ok(VolumeDescriptor {
+        label: String::from(label.trim_end_matches([' ', '\0'])),
+        created: parse_date(&pvd[ISO_CREATION_DATE..ISO_CREATION_DATE + ISO_DATE_SIZE]),
+        size: blocks as u64 * block_size as u64,
+    })
+}
+
+/// Reads volume label from primary volume descriptor of ISO9660 `image`.
+pub fn read_volume_label<T: Read + Seek>(image: T) -> Result<String> {
+    read_volume_descriptor(image).map(|d| d.label)
+}
+
+/// Tells whether `image` of `size` bytes is dual-layer DVD9.
+///
+/// Second layer is found by its volume descriptor, laid out as described at
+/// [`Layers`]. Images too large for DVD5 are still taken as dual-layer,
+/// even without it.
+pub fn read_layers<T: Read + Seek>(mut image: T, size: u64) -> Result<Option<Layers>> {
+    let pvd = read_volume_descriptor(&mut image)?;
+    if size <= pvd.size {
+        return Ok(None);
+    }
+
+    let mut sector = vec![0x00; ISO_SECTOR_SIZE];
+    image.seek(SeekFrom::Start(pvd.size))?;
+    let second = image.read_exact(&mut sector).is_ok() && sector.starts_with(ISO_PVD_MAGIC);
+
+    let layer_break = pvd.size / ISO_SECTOR_SIZE as u64 - ISO_PVD_SECTOR;
+    match (second, size > DVD5_SIZE) {
+        (true, _) => Ok(Some(Layers {
+            layer_break: Some(layer_break),
+        })),
+        (false, true) => Ok(Some(Layers { layer_break: None })),
+        (false, false) => Ok(None),
+    }
+}
+
+/// Tells uncompressed size of image at `path`, looking inside archives too.
+///
+/// Gzip and xz streams do not tell their size upfront, so for these
+/// the archive size stands in as the least the image will take.
+pub fn image_size(path: &Path) -> Result<u64> {
+    match source::with_image_start(path, |start| Ok(start.size))? {
+        Some(size) => Ok(size),
+        None => Ok(fs::metadata(path)?.len()),
+    }
+}
+
 impl From<PathBuf> for ISOChunk {
     fn from(path: PathBuf) -> Self {
         ISOChunk { path }
@@ -38,34 +158,12 @@ impl From<PathBuf> for GameChunk {
 
 impl Chunk for ISOChunk {
     fn serial(&self) -> Result<String> {
-        let isofile = File::open(&self.path)?;
-        let iso = match ISO9660::new(isofile) {
-            Ok(image) => image,
-            Err(_) => return Err(Error::from(ErrorKind::InvalidData)),
-        };
-
-        match iso.open(SYSTEM_CNF_PATH).unwrap() {
-            Some(DirectoryEntry::File(file)) => {
-                let mut buffer = String::new();
-                file.read().read_to_string(&mut buffer)?;
-
-                // first line of SYSTEM.CNF should go like:
-                // BOOT2 = cdrom0:\SLXS_XXX.XX;1
-                // and we are fetching SLXS_XXX.XX from it
-                let re = Regex::new("([^:\\\\;]+)(:?;1)?$").unwrap();
-                let boot_path = buffer.lines().next().unwrap();
-                let boot_file = re.captures(boot_path).unwrap();
-
-                let serial = boot_file.get(1).unwrap().as_str();
-                Ok(String::from(serial))
-            }
-            _ => Err(Error::from(ErrorKind::NotFound)),
-        }
+        let image = source::open(&self.path)?;
+        read_serial(image)
     }
 
     fn size(&self) -> Result<u64> {
-        let metadata = fs::metadata(&self.path)?;
-        Ok(metadata.len())
+        image_size(&self.path)
     }
 
     fn path(&self) -> &Path {
@@ -117,6 +215,54 @@ mod tests {
         assert_eq!(serial, String::from("SLXS_123.45"));
     }
 
+    #[test]
+    fn test_isochunk_size_of_archive() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.zip");
+        assert!(fs::metadata(&path).unwrap().len() < 358400);
+        assert_eq!(ISOChunk::from(path).size().unwrap(), 358400);
+    }
+
+    #[test]
+    fn test_read_volume_label() {
+        let mut isopath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        isopath.push("resources/testimage.iso");
+        let label = |p: &Path| source::with_image_start(p, |s| read_volume_label(s.image));
+        assert_eq!(label(&isopath).unwrap(), "CDROM");
+
+        isopath.set_extension("iso.gz");
+        assert_eq!(label(&isopath).unwrap(), "CDROM");
+    }
+
+    #[test]
+    fn test_read_volume_descriptor() {
+        let mut isopath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        isopath.push("resources/testimage.iso");
+        let pvd = read_volume_descriptor(source::open(&isopath).unwrap()).unwrap();
+        assert_eq!(pvd.label, "CDROM");
+        assert_eq!(pvd.created.as_deref(), Some("2022-03-06 12:25:57"));
+        assert_eq!(pvd.size, 358400);
+        assert!(parse_date(b"0000000000000000").is_none());
+    }
+
+    #[test]
+    fn test_read_layers() {
+        let mut isopath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        isopath.push("resources/testimage.iso");
+        let mut image = fs::read(isopath).unwrap();
+        let size = image.len() as u64;
+        assert!(read_layers(std::io::Cursor::new(&image), size)
+            .unwrap()
+            .is_none());
+
+        // Second layer volume descriptor goes right after first layer volume.
+        let pvd = image[16 * ISO_SECTOR_SIZE..17 * ISO_SECTOR_SIZE].to_vec();
+        image.extend(pvd);
+        let size = image.len() as u64;
+        let layers = read_layers(std::io::Cursor::new(&image), size).unwrap();
+        assert_eq!(layers.unwrap().layer_break, Some(159));
+    }
+
     #[test]
     fn test_isochunk_get_serial_file_not_found() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -136,6 +282,61 @@ mod tests {
         assert_eq!(size, expected_size);
     }
 
+    #[test]
+    fn test_isochunk_get_serial_ciso() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let isochunk = ISOChunk::from(path);
+        let serial = isochunk.serial().unwrap();
+        assert_eq!(serial, String::from("SLXS_123.45"));
+    }
+
+    #[test]
+    fn test_isochunk_get_serial_ziso() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.zso");
+        let isochunk = ISOChunk::from(path);
+        let serial = isochunk.serial().unwrap();
+        assert_eq!(serial, String::from("SLXS_123.45"));
+    }
+
+    #[test]
+    fn test_isochunk_get_serial_chd() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.chd");
+        let isochunk = ISOChunk::from(path);
+        let serial = isochunk.serial().unwrap();
+        assert_eq!(serial, String::from("SLXS_123.45"));
+    }
+
+    #[test]
+    fn test_isochunk_get_serial_chd_cd() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cd.chd");
+        let isochunk = ISOChunk::from(path);
+        let serial = isochunk.serial().unwrap();
+        assert_eq!(serial, String::from("SLXS_123.45"));
+    }
+
+    #[test]
+    fn test_isochunk_get_serial_cue() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cue");
+        let isochunk = ISOChunk::from(path);
+        let serial = isochunk.serial().unwrap();
+        assert_eq!(serial, String::from("SLXS_123.45"));
+    }
+
+    #[test]
+    fn test_isochunk_get_size_ciso() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let isochunk = ISOChunk::from(path);
+        let size = isochunk.size().unwrap();
+        let expected_size = 358400;
+        assert_eq!(size, expected_size);
+    }
+
     #[test]
     fn test_isochunk_get_size_file_not_found() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+const SCEC_CD_MEDIA_TYPE: u8 = 0x12;
+const SCEC_DVD_MEDIA_TYPE: u8 = 0x14;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaType {
+    Cd,
+    Dvd,
+}
+
+impl From<u8> for MediaType {
+    fn from(media: u8) -> Self {
+        match media {
+            SCEC_CD_MEDIA_TYPE => MediaType::Cd,
+            _ => MediaType::Dvd,
+        }
+    }
+}
+
+impl From<MediaType> for u8 {
+    fn from(media: MediaType) -> Self {
+        match media {
+            MediaType::Cd => SCEC_CD_MEDIA_TYPE,
+            MediaType::Dvd => SCEC_DVD_MEDIA_TYPE,
+        }
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaType::Cd => write!(f, "CD"),
+            MediaType::Dvd => write!(f, "DVD"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_from_byte() {
+        assert_eq!(MediaType::from(0x12), MediaType::Cd);
+        assert_eq!(MediaType::from(0x14), MediaType::Dvd);
+    }
+
+    #[test]
+    fn test_media_to_byte() {
+        assert_eq!(u8::from(MediaType::Cd), 0x12);
+        assert_eq!(u8::from(MediaType::Dvd), 0x14);
+    }
+}
@@ -1,19 +1,34 @@
+pub mod art;
+pub mod cheats;
+pub mod classify;
+pub mod config;
 mod crc;
-mod iso;
+pub mod iso;
+pub mod media;
+pub mod name;
+pub mod opl;
+pub mod preflight;
+pub mod source;
+pub mod systemcnf;
+pub mod titles;
 
 use crate::game::iso::{Chunk, GameChunk, ISOChunk};
+use crate::game::media::MediaType;
+use crate::game::source::archive;
+use crate::game::source::stream::StreamImage;
 
-use std::fs::{read_dir, remove_file, File};
+use std::fs::{create_dir_all, read_dir, remove_file, File};
 use std::io::prelude::*;
-use std::io::{copy, stdout, Result, SeekFrom};
+use std::io::{copy, stdout, BufReader, Result, SeekFrom};
 use std::io::{Error, ErrorKind};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 const CHUNK_SIZE: u64 = 1_073_741_824;
 
 pub struct Game {
     pub opl_name: String,
     crc_name: String,
+    media: MediaType,
     chunks: Vec<Box<dyn Chunk>>,
 }
 
@@ -26,11 +41,23 @@ impl Game {
         Game {
             opl_name,
             crc_name,
+            media: MediaType::Dvd,
             chunks,
         }
     }
 
-    pub fn from_config(path: &Path, opl_name: String) -> Self {
+    pub fn from_stream(opl_name: String) -> Self {
+        let crc_name = crc::get_game_name_crc(&opl_name);
+
+        Game {
+            opl_name,
+            crc_name,
+            media: MediaType::Dvd,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn from_config(path: &Path, opl_name: String, media: MediaType) -> Self {
         let crc_name = crc::get_game_name_crc(&opl_name);
         let chunks = list_game_chunks(path, &crc_name)
             .unwrap_or_default()
@@ -44,45 +71,81 @@ impl Game {
         Game {
             opl_name,
             crc_name,
+            media,
             chunks,
         }
     }
 
     pub fn create_chunks(&mut self, dstpath: &Path) -> Result<()> {
         let image = self.chunks.pop().ok_or(ErrorKind::NotFound)?;
-        let mut file = File::open(image.path())?;
-        let mut offset: u64 = 0;
 
         if !self.chunks.is_empty() {
             // Any elements left would mean we are splitting already split image.
             return Err(Error::from(ErrorKind::InvalidInput));
         }
 
-        // TODO Simplify when https://github.com/rust-lang/rust/issues/88581 closes.
-        let mut n_chunks = file.metadata().unwrap().len() / CHUNK_SIZE;
-        if file.metadata().unwrap().len() % CHUNK_SIZE > 0 {
-            n_chunks += 1;
+        if let Some(kind) = archive::detect(image.path())? {
+            return archive::with_image(image.path(), kind, |stream| {
+                self.create_chunks_from_stream(stream, dstpath)
+            });
         }
 
-        for chunk in 0..n_chunks {
-            print!("Creating chunk {} of {}...", chunk + 1, n_chunks);
+        let mut file = source::open(image.path())?;
+        let serial = iso::read_serial(&mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+        self.media = file.media();
+
+        let size = file.size();
+        self.write_chunks(&mut file, &serial, Some(size), dstpath)
+    }
+
+    pub fn create_chunks_from_stream(
+        &mut self,
+        mut stream: StreamImage,
+        dstpath: &Path,
+    ) -> Result<()> {
+        let serial = iso::read_serial(stream.prefix())?;
+        let size = stream.size();
+        self.media = stream.media();
+        self.write_chunks(&mut stream, &serial, size, dstpath)
+    }
+
+    fn write_chunks(
+        &mut self,
+        src: &mut dyn Read,
+        serial: &str,
+        size: Option<u64>,
+        dstpath: &Path,
+    ) -> Result<()> {
+        // Image is read strictly sequentially, so it can come from a pipe.
+        let mut src = BufReader::new(src);
+        let n_chunks = size.map(|s| s.div_ceil(CHUNK_SIZE));
+        let mut written: u64 = 0;
+        let mut chunk = 0;
+
+        while !src.fill_buf()?.is_empty() {
+            match n_chunks {
+                Some(n) => print!("Creating chunk {} of {}...", chunk + 1, n),
+                None => print!("Creating chunk {}...", chunk + 1),
+            }
             stdout().flush().unwrap();
 
             // Even largest PS2 game should not be over 9 chunks.
-            let chunkname = format!("ul.{}.{}.0{}", &self.crc_name, &image.serial()?, chunk);
+            let chunkname = format!("ul.{}.{}.0{}", &self.crc_name, serial, chunk);
             let chunkpath = dstpath.join(&chunkname);
             let mut dst = File::create(&chunkpath)?;
-
-            file.seek(SeekFrom::Start(offset))?;
-            let mut src = file.take(CHUNK_SIZE);
-            copy(&mut src, &mut dst)?;
-            file = src.into_inner();
+            written += copy(&mut (&mut src).take(CHUNK_SIZE), &mut dst)?;
 
             self.chunks.push(Box::new(GameChunk::from(chunkpath)));
-            offset += CHUNK_SIZE;
+            chunk += 1;
             println!("Done.");
         }
 
+        if written == 0 || size.is_some_and(|s| s != written) {
+            // Stream ended before declared size (or went past it).
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
         Ok(())
     }
 
@@ -99,38 +162,85 @@ impl Game {
         Ok(())
     }
 
+    pub fn export_chunks(&self, target: &Path) -> Result<PathBuf> {
+        // OPL looks for images in DVD/ or CD/ named like SLUS_123.45.Name.iso
+        let dstdir = target.join(self.media.to_string());
+        let isopath = dstdir.join(opl::compose_iso_name(&self.serial(), &self.opl_name));
+
+        if self.chunks.is_empty() {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        if isopath.exists() {
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+
+        create_dir_all(&dstdir)?;
+        let mut dst = File::create(&isopath)?;
+        println!("Exporting {} to {}", &self.opl_name, isopath.display());
+
+        for (num, chunk) in self.chunks.iter().enumerate() {
+            print!("Copying chunk {} of {}...", num + 1, self.chunks.len());
+            stdout().flush().unwrap();
+            let mut src = File::open(chunk.path())?;
+            copy(&mut src, &mut dst)?;
+            println!("Done.");
+        }
+
+        Ok(isopath)
+    }
+
     pub fn serial(&self) -> String {
+        self.read_serial()
+            .unwrap_or_else(|_| String::from("NOT FOUND"))
+    }
+
+    /// Serial read from first chunk, failing if it cannot be read.
+    pub fn read_serial(&self) -> Result<String> {
         self.chunks
             .get(0)
             .and_then(|c| c.serial().ok())
-            .ok_or(ErrorKind::InvalidData)
-            .unwrap_or_else(|_| String::from("NOT FOUND"))
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))
     }
 
-    pub fn num_chunks(&self) -> u8 {
-        self.chunks.len() as u8
+    pub fn media(&self) -> MediaType {
+        self.media
     }
 
-    pub fn formatted_size(&self) -> String {
-        let total_size: u64 = self
-            .chunks
-            .iter()
-            .map(|c| c.size().unwrap_or(0))
-            .collect::<Vec<u64>>()
+    pub fn crc_name(&self) -> &str {
+        &self.crc_name
+    }
+
+    /// Chunk files with their sizes, in the order image is made of.
+    pub fn chunk_files(&self) -> Vec<(&Path, u64)> {
+        self.chunks
             .iter()
-            .sum();
+            .map(|c| (c.path(), c.size().unwrap_or(0)))
+            .collect()
+    }
 
-        let size_gb = total_size as f64 / 1_000_000_000.0;
-        format!("{:.2}GB", size_gb)
+    pub fn set_media(&mut self, media: MediaType) {
+        self.media = media;
+    }
+
+    pub fn num_chunks(&self) -> u8 {
+        self.chunks.len() as u8
+    }
+
+    pub fn size(&self) -> u64 {
+        self.chunks.iter().map(|c| c.size().unwrap_or(0)).sum()
     }
 }
 
 fn list_game_chunks(path: &Path, crc_name: &str) -> Result<Vec<String>> {
-    let chunks = read_dir(path)?
+    let mut chunks = read_dir(path)?
         .map(|res| res.unwrap().file_name().into_string().unwrap())
         .filter(|n| n.contains(crc_name))
         .collect::<Vec<_>>();
 
+    // Chunks have to stay in order for the image to be reassembled.
+    chunks.sort();
+
     if chunks.is_empty() {
         return Err(Error::from(ErrorKind::NotFound));
     }
@@ -141,7 +251,7 @@ fn list_game_chunks(path: &Path, crc_name: &str) -> Result<Vec<String>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::testutil::TestDir;
 
     #[test]
     fn test_list_game_chunks() {
@@ -169,11 +279,53 @@ mod tests {
         assert!(game.create_chunks(&dst).is_err());
     }
 
+    #[test]
+    fn test_create_chunks_from_stream() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let file = File::open(&path).unwrap();
+        let stream = StreamImage::new(Box::new(file), Some(1024)).unwrap();
+        let mut game = Game::from_stream(String::from("foobarbaz"));
+        // Whole test image fits into stream prefix, so its real size wins.
+        assert_eq!(stream.size(), Some(358400));
+
+        let dst = TestDir::new("stream");
+        assert!(game.create_chunks_from_stream(stream, dst.path()).is_ok());
+        assert_eq!(game.num_chunks(), 1);
+        assert_eq!(game.serial(), "SLXS_123.45");
+    }
+
+    #[test]
+    fn test_export_chunks() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources");
+        let name = format!("f{}", "o".repeat(31));
+        let game = Game::from_config(&path, name.clone(), MediaType::Cd);
+        let dst = TestDir::new("export");
+        let isopath = game.export_chunks(dst.path()).unwrap();
+
+        let expected = dst.join(format!("CD/SLXS_123.45.{}.iso", name));
+        assert_eq!(isopath, expected);
+        assert_eq!(std::fs::metadata(&isopath).unwrap().len(), 20);
+        assert!(game.export_chunks(dst.path()).is_err());
+    }
+
+    #[test]
+    fn test_export_chunks_no_chunks() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources");
+        let game = Game::from_config(&path, String::from("foobarbaz"), MediaType::Dvd);
+        let dir = TestDir::new("export_empty");
+        let dst = dir.join("export");
+        assert!(game.export_chunks(&dst).is_err());
+        assert!(!dst.exists());
+    }
+
     #[test]
     fn test_delete_chunks_no_chunks() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("resources");
-        let game = Game::from_config(&path, String::from("foobarbaz"));
+        let game = Game::from_config(&path, String::from("foobarbaz"), MediaType::Dvd);
         assert_eq!(game.num_chunks(), 0);
         assert!(game.delete_chunks().is_ok());
     }
@@ -0,0 +1,301 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// OPL name cannot be longer than 32 bytes.
+pub const OPL_NAME_SIZE: usize = 32;
+
+// Serial prefixes of each region, e.g. SLUS_200.62 is North American.
+const REGIONS: [(&str, &[&str]); 4] = [
+    ("NTSC-U", &["SLUS", "SCUS"]),
+    ("PAL", &["SLES", "SCES", "SCED", "SLED"]),
+    (
+        "NTSC-J",
+        &[
+            "SLPS", "SLPM", "SCPS", "SCPM", "SCAJ", "SLAJ", "PBPX", "PAPX",
+        ],
+    ),
+    ("NTSC-K", &["SLKA", "SCKA"]),
+];
+
+// ASCII spelling of accented and typographic characters OPL font lacks.
+const TRANSLITERATIONS: [(&str, &str); 34] = [
+    ("ÀÁÂÃÄÅĀĂĄ", "A"),
+    ("àáâãäåāăą", "a"),
+    ("ÇĆĈĊČ", "C"),
+    ("çćĉċč", "c"),
+    ("ÐĎĐ", "D"),
+    ("ðďđ", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"),
+    ("èéêëēĕėęě", "e"),
+    ("ĜĞĠĢ", "G"),
+    ("ĝğġģ", "g"),
+    ("ÌÍÎÏĨĪĬĮİ", "I"),
+    ("ìíîïĩīĭįı", "i"),
+    ("ŁĹĻĽ", "L"),
+    ("łĺļľ", "l"),
+    ("ÑŃŅŇ", "N"),
+    ("ñńņň", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"),
+    ("òóôõöøōŏő", "o"),
+    ("ŔŖŘ", "R"),
+    ("ŕŗř", "r"),
+    ("ŚŜŞŠ", "S"),
+    ("śŝşš", "s"),
+    ("ŢŤ", "T"),
+    ("ţť", "t"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"),
+    ("ùúûüũūŭůűų", "u"),
+    ("ÝŸ", "Y"),
+    ("ýÿ", "y"),
+    ("ŹŻŽ", "Z"),
+    ("źżž", "z"),
+    ("‘’‚′", "'"),
+    ("“”„″", "\""),
+    ("‐‑‒–—―", "-"),
+    ("\u{3000}\u{a0}", " "),
+];
+
+// Characters spelled with more than one letter.
+const LIGATURES: [(char, &str); 10] = [
+    ('Æ', "AE"),
+    ('æ', "ae"),
+    ('Œ', "OE"),
+    ('œ', "oe"),
+    ('ß', "ss"),
+    ('Þ', "Th"),
+    ('þ', "th"),
+    ('…', "..."),
+    ('™', "TM"),
+    ('©', "(C)"),
+];
+
+// Full-width forms of ASCII, as used in Japanese titles.
+const FULLWIDTH_FIRST: char = '\u{ff01}';
+const FULLWIDTH_LAST: char = '\u{ff5e}';
+const FULLWIDTH_OFFSET: u32 = 0xfee0;
+
+/// Values template fields are filled with.
+#[derive(Clone, Debug, Default)]
+pub struct NameFields {
+    pub stem: String,
+    pub serial: String,
+    pub region: String,
+    pub label: String,
+    pub title: String,
+}
+
+/// Region of game, told by prefix of its serial.
+pub fn region_from_serial(serial: &str) -> Option<&'static str> {
+    let prefix = serial.get(..4)?.to_ascii_uppercase();
+    REGIONS
+        .iter()
+        .find(|(_, prefixes)| prefixes.contains(&prefix.as_str()))
+        .map(|(region, _)| *region)
+}
+
+/// Fills `{stem}`, `{serial}`, `{region}`, `{label}` and `{title}` in `template`.
+pub fn render(template: &str, fields: &NameFields) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+        name.push_str(&rest[..start]);
+
+        let value = match &rest[start + 1..start + end] {
+            "stem" => &fields.stem,
+            "serial" => &fields.serial,
+            "region" => &fields.region,
+            "label" => &fields.label,
+            "title" => &fields.title,
+            _ => return Err(Error::from(ErrorKind::InvalidInput)),
+        };
+
+        name.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+
+    name.push_str(rest);
+
+    // Empty fields may leave doubled or dangling spaces and brackets.
+    let name = name.replace("[]", "").replace("()", "");
+    Ok(name.split_whitespace().collect::<Vec<&str>>().join(" "))
+}
+
+/// Removes tags like `(USA)` or `(En,Fr,Es)` from `text`.
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut depth = 0;
+
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            c if depth == 0 => stripped.push(c),
+            _ => (),
+        }
+    }
+
+    stripped.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Drops last word of `text`, with punctuation left dangling before it.
+fn drop_word(text: &str) -> Option<String> {
+    let (rest, _) = text.trim_end().rsplit_once(' ')?;
+    Some(String::from(
+        rest.trim_end_matches([' ', '-', ':', ',', '&']),
+    ))
+}
+
+/// Cuts `name` to at most `size` bytes without splitting characters.
+pub fn truncate(name: &str, size: usize) -> String {
+    let mut end = size.min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    String::from(name[..end].trim_end())
+}
+
+/// Spells character with ASCII characters OPL font can show.
+///
+/// Characters without such spelling, e.g. kanji, yield empty string.
+fn transliterate(c: char) -> String {
+    if c.is_ascii_whitespace() {
+        return String::from(" ");
+    }
+
+    if c.is_ascii() {
+        return match c.is_ascii_control() {
+            true => String::new(),
+            false => String::from(c),
+        };
+    }
+
+    if (FULLWIDTH_FIRST..=FULLWIDTH_LAST).contains(&c) {
+        let ascii = char::from_u32(c as u32 - FULLWIDTH_OFFSET).unwrap();
+        return String::from(ascii);
+    }
+
+    if let Some((_, ascii)) = TRANSLITERATIONS.iter().find(|(chars, _)| chars.contains(c)) {
+        return String::from(*ascii);
+    }
+
+    LIGATURES
+        .iter()
+        .find(|(l, _)| *l == c)
+        .map(|(_, ascii)| String::from(*ascii))
+        .unwrap_or_default()
+}
+
+/// Turns `name` into one OPL can show: transliterated to printable ASCII,
+/// with whitespace collapsed and cut to 32 bytes.
+pub fn normalize_name(name: &str) -> String {
+    let ascii = name.chars().map(transliterate).collect::<String>();
+    let collapsed = ascii.split_whitespace().collect::<Vec<&str>>().join(" ");
+    truncate(&collapsed, OPL_NAME_SIZE)
+}
+
+/// Renders `template` and shortens it to fit OPL name limit.
+///
+/// Parenthetical tags are dropped from stem, label and title first, then
+/// words from the end of the longest of them, and only then is the name cut.
+pub fn make_name(template: &str, fields: &NameFields) -> Result<String> {
+    let mut fields = fields.clone();
+    let name = render(template, &fields)?;
+    if name.len() <= OPL_NAME_SIZE {
+        return Ok(name);
+    }
+
+    // Only free text fields used by template are worth shortening.
+    let used = |key: &str| template.contains(&format!("{{{}}}", key));
+    let (stem, label, title) = (used("stem"), used("label"), used("title"));
+
+    for text in [&mut fields.stem, &mut fields.label, &mut fields.title] {
+        *text = strip_tags(text);
+    }
+
+    loop {
+        let name = render(template, &fields)?;
+        if name.len() <= OPL_NAME_SIZE {
+            return Ok(name);
+        }
+
+        let longest = [
+            (stem, &mut fields.stem),
+            (label, &mut fields.label),
+            (title, &mut fields.title),
+        ]
+        .into_iter()
+        .filter(|(used, text)| *used && text.contains(' '))
+        .map(|(_, text)| text)
+        .max_by_key(|t| t.len());
+
+        match longest.and_then(|t| Some((drop_word(t)?, t))) {
+            Some((shorter, text)) => *text = shorter,
+            None => return Ok(truncate(&name, OPL_NAME_SIZE)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(title: &str) -> NameFields {
+        NameFields {
+            stem: String::from("Foo (USA) (En,Fr,Es)"),
+            serial: String::from("SLUS_200.62"),
+            region: String::from(region_from_serial("SLUS_200.62").unwrap()),
+            label: String::from("FOO"),
+            title: String::from(title),
+        }
+    }
+
+    #[test]
+    fn test_region_from_serial() {
+        assert_eq!(region_from_serial("SLES_503.30"), Some("PAL"));
+        assert_eq!(region_from_serial("slpm_650.10"), Some("NTSC-J"));
+        assert_eq!(region_from_serial("ABCD_123.45"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let name = render("{title} [{region}]", &fields("Foo")).unwrap();
+        assert_eq!(name, "Foo [NTSC-U]");
+        let name = render("{serial} {title} ({label})", &fields("")).unwrap();
+        assert_eq!(name, "SLUS_200.62 (FOO)");
+        assert!(render("{name}", &fields("Foo")).is_err());
+        assert!(render("{title", &fields("Foo")).is_err());
+    }
+
+    #[test]
+    fn test_make_name_shortens() {
+        let name = make_name("{stem}", &fields("")).unwrap();
+        assert_eq!(name, "Foo (USA) (En,Fr,Es)");
+
+        let long = fields("Tom Clancy's Splinter Cell: Pandora Tomorrow (Special)");
+        let name = make_name("{title} [{region}]", &long).unwrap();
+        assert_eq!(name, "Tom Clancy's Splinter [NTSC-U]");
+
+        let word = fields("Supercalifragilisticexpialidocious-Supercalifragilistic");
+        let name = make_name("{title}", &word).unwrap();
+        assert_eq!(name.len(), OPL_NAME_SIZE);
+        assert_eq!(truncate("Café", 4), "Caf");
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Pokémon Crêpe Æon"), "Pokemon Crepe AEon");
+        assert_eq!(normalize_name("ＦＩＦＡ　２００５"), "FIFA 2005");
+        assert_eq!(normalize_name("ファイナル Fantasy\tX"), "Fantasy X");
+        assert_eq!(
+            normalize_name("Tom’s Game – Remix…"),
+            "Tom's Game - Remix..."
+        );
+
+        let long = normalize_name("Ééééééééééééééééééééééééééééééééééé");
+        assert_eq!(long.len(), OPL_NAME_SIZE);
+    }
+}
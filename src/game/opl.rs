@@ -0,0 +1,51 @@
+use regex::Regex;
+
+// OPL names images in DVD/ and CD/ folders like SLUS_123.45.Name.iso
+const OPL_ISO_PATTERN: &str = "^([A-Za-z]{4}_\\d{3}\\.\\d{2})\\.(.+)\\.[iI][sS][oO]$";
+
+/// Composes OPL file name out of game `serial` and `title`.
+pub fn compose_iso_name(serial: &str, title: &str) -> String {
+    format!("{}.{}.iso", serial, sanitize_filename(title))
+}
+
+/// Splits OPL file name into game serial and title.
+pub fn parse_iso_name(name: &str) -> Option<(String, String)> {
+    let re = Regex::new(OPL_ISO_PATTERN).unwrap();
+    let caps = re.captures(name)?;
+
+    Some((String::from(&caps[1]), String::from(&caps[2])))
+}
+
+fn sanitize_filename(name: &str) -> String {
+    // Characters FAT32/exFAT (and SMB shares) don't allow in file names.
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_iso_name() {
+        let name = compose_iso_name("SLUS_123.45", "Foo: Bar?");
+        assert_eq!(name, "SLUS_123.45.Foo_ Bar_.iso");
+    }
+
+    #[test]
+    fn test_parse_iso_name() {
+        let (serial, title) = parse_iso_name("SLES_503.30.Foo (v1.01).ISO").unwrap();
+        assert_eq!(serial, "SLES_503.30");
+        assert_eq!(title, "Foo (v1.01)");
+    }
+
+    #[test]
+    fn test_parse_iso_name_no_serial() {
+        assert!(parse_iso_name("Foo (USA).iso").is_none());
+        assert!(parse_iso_name("SLUS_123.45.Foo.bin").is_none());
+    }
+}
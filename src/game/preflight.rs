@@ -0,0 +1,236 @@
+use crate::game::iso::read_volume_descriptor;
+use crate::game::systemcnf::SystemCnf;
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+const SECTOR_SIZE: u64 = 2048;
+const PVD_SECTOR: u64 = 16;
+const PVD_ROOT_RECORD: usize = 156;
+// Offsets within ISO9660 directory record.
+const RECORD_EXTENT: usize = 2;
+const RECORD_SIZE: usize = 10;
+const RECORD_FLAGS: usize = 25;
+const RECORD_NAME_LENGTH: usize = 32;
+const RECORD_NAME: usize = 33;
+const DIRECTORY_FLAG: u8 = 0x02;
+// PS2 discs keep few files per directory, more than this is corruption.
+const MAX_DIRECTORY_SIZE: u64 = 1024 * 1024;
+
+/// Sign of image being truncated or corrupt, found before it is split.
+#[derive(Debug, PartialEq)]
+pub enum Defect {
+    Misaligned(u64),
+    Truncated { size: u64, volume: u64 },
+    NoSystemCnf,
+    MissingBoot(String),
+    DamagedBoot(String),
+}
+
+impl fmt::Display for Defect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Defect::Misaligned(size) => {
+                write!(
+                    f,
+                    "Image size {} is not multiple of 2048-byte sectors",
+                    size
+                )
+            }
+            Defect::Truncated { size, volume } => write!(
+                f,
+                "Image has {} bytes, but its volume descriptor says {}",
+                size, volume
+            ),
+            Defect::NoSystemCnf => write!(f, "SYSTEM.CNF could not be read"),
+            Defect::MissingBoot(path) => {
+                write!(f, "Boot executable {} named in SYSTEM.CNF is missing", path)
+            }
+            Defect::DamagedBoot(path) => {
+                write!(f, "Boot executable {} does not fit within image", path)
+            }
+        }
+    }
+}
+
+/// Tells first sector and size in bytes of extent directory `record` points to.
+fn record_extent(record: &[u8]) -> (u64, u64) {
+    let get = |offset: usize| u32::from_le_bytes(record[offset..offset + 4].try_into().unwrap());
+    (get(RECORD_EXTENT) as u64, get(RECORD_SIZE) as u64)
+}
+
+/// Looks for record of `name` in raw `directory` extent.
+fn find_record(directory: &[u8], name: &str, is_dir: bool) -> Option<(u64, u64)> {
+    let mut offset = 0;
+
+    while offset < directory.len() {
+        let length = directory[offset] as usize;
+        if length == 0 {
+            // Records do not cross sectors, rest of this one is padding.
+            offset = (offset / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+            continue;
+        }
+
+        let record = directory.get(offset..offset + length)?;
+        let name_length = *record.get(RECORD_NAME_LENGTH)? as usize;
+        let identifier = record.get(RECORD_NAME..RECORD_NAME + name_length)?;
+        let identifier = String::from_utf8_lossy(identifier);
+        let identifier = identifier.split(';').next().unwrap().trim_end_matches('.');
+
+        let found_dir = record[RECORD_FLAGS] & DIRECTORY_FLAG != 0;
+        if found_dir == is_dir && identifier.eq_ignore_ascii_case(name) {
+            return Some(record_extent(record));
+        }
+
+        offset += length;
+    }
+
+    None
+}
+
+/// Finds first sector and size of file at `path` by walking directory
+/// records of ISO9660 `image`, without reading the file itself.
+fn find_extent<T: Read + Seek>(mut image: T, path: &str) -> Result<Option<(u64, u64)>> {
+    let mut pvd = vec![0x00; SECTOR_SIZE as usize];
+    image.seek(SeekFrom::Start(PVD_SECTOR * SECTOR_SIZE))?;
+    image.read_exact(&mut pvd)?;
+
+    let components = path
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    let mut extent = record_extent(&pvd[PVD_ROOT_RECORD..]);
+
+    for (i, component) in components.iter().enumerate() {
+        let (sector, size) = extent;
+        if size > MAX_DIRECTORY_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let mut directory = vec![0x00; size as usize];
+        image.seek(SeekFrom::Start(sector * SECTOR_SIZE))?;
+        image.read_exact(&mut directory)?;
+
+        let is_dir = i + 1 < components.len();
+        match find_record(&directory, component, is_dir) {
+            Some(found) => extent = found,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(extent))
+}
+
+/// Checks boot executable named in `SYSTEM.CNF` lies within `size` bytes.
+fn check_boot<T: Read + Seek>(mut image: T, size: u64) -> Result<Option<Defect>> {
+    let cnf = match SystemCnf::read(&mut image) {
+        Ok(cnf) => cnf,
+        Err(_) => return Ok(Some(Defect::NoSystemCnf)),
+    };
+
+    let boot = cnf.boot_file().replace('\\', "/");
+    match find_extent(image, &boot) {
+        Ok(Some((sector, length))) if sector * SECTOR_SIZE + length > size => {
+            Ok(Some(Defect::DamagedBoot(boot)))
+        }
+        Ok(Some(_)) => Ok(None),
+        _ => Ok(Some(Defect::MissingBoot(boot))),
+    }
+}
+
+/// Checks ISO9660 `image` of `size` bytes against its primary volume
+/// descriptor and `SYSTEM.CNF`, returning every defect found.
+pub fn preflight<T: Read + Seek>(mut image: T, size: u64) -> Result<Vec<Defect>> {
+    let mut defects = Vec::new();
+
+    if size % SECTOR_SIZE != 0 {
+        defects.push(Defect::Misaligned(size));
+    }
+
+    let pvd = read_volume_descriptor(&mut image)?;
+    if size < pvd.size {
+        defects.push(Defect::Truncated {
+            size,
+            volume: pvd.size,
+        });
+    }
+
+    image.seek(SeekFrom::Start(0))?;
+    defects.extend(check_boot(image, size)?);
+
+    Ok(defects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn testimage() -> Vec<u8> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        read(path).unwrap()
+    }
+
+    #[test]
+    fn test_preflight_missing_boot() {
+        // Test image has SYSTEM.CNF, but no executable it points to.
+        let image = testimage();
+        let size = image.len() as u64;
+        let defects = preflight(Cursor::new(image), size).unwrap();
+        assert_eq!(
+            defects,
+            vec![Defect::MissingBoot(String::from("SLXS_123.45"))]
+        );
+    }
+
+    #[test]
+    fn test_find_extent() {
+        let image = testimage();
+        let extent = find_extent(Cursor::new(&image), "system.cnf").unwrap();
+        let (sector, length) = extent.unwrap();
+        assert_eq!(sector, 24);
+        assert!(length > 0);
+
+        assert_eq!(
+            find_extent(Cursor::new(&image), "SLXS_123.45").unwrap(),
+            None
+        );
+        assert_eq!(
+            find_extent(Cursor::new(&image), "FOO/SYSTEM.CNF").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_boot_past_end() {
+        // Whole image is readable, but given size ends before the executable.
+        let mut image = testimage();
+        let cnf = b"BOOT2 = cdrom0:\\SYSTEM.CNF;1\r\n";
+        image[24 * 2048..24 * 2048 + cnf.len()].copy_from_slice(cnf);
+
+        let boot = check_boot(Cursor::new(&image), 24 * 2048).unwrap();
+        assert_eq!(boot, Some(Defect::DamagedBoot(String::from("SYSTEM.CNF"))));
+        let size = image.len() as u64;
+        assert_eq!(check_boot(Cursor::new(&image), size).unwrap(), None);
+    }
+
+    #[test]
+    fn test_preflight_truncated() {
+        let mut image = testimage();
+        image.truncate(100_000);
+        let defects = preflight(Cursor::new(image), 100_000).unwrap();
+        assert_eq!(defects[0], Defect::Misaligned(100_000));
+        assert_eq!(
+            defects[1],
+            Defect::Truncated {
+                size: 100_000,
+                volume: 358_400
+            }
+        );
+    }
+}
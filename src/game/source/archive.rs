@@ -0,0 +1,200 @@
+use crate::game::source::stream::StreamImage;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{sink, BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use lzma_rust2::XzReader;
+use sevenz_rust::{Password, SevenZReader};
+use zip::ZipArchive;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const SEVENZ_MAGIC: [u8; 6] = [0x37, 0x7a, 0xbc, 0xaf, 0x27, 0x1c];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+const ISO_EXTENSION: &str = ".iso";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    SevenZip,
+    Gzip,
+    Xz,
+}
+
+/// Tells whether file at `path` is an archive, based on its magic bytes.
+pub fn detect(path: &Path) -> Result<Option<ArchiveKind>> {
+    let mut magic = Vec::new();
+    File::open(path)?.take(6).read_to_end(&mut magic)?;
+
+    let kind = match magic {
+        ref m if m.starts_with(&ZIP_MAGIC) => Some(ArchiveKind::Zip),
+        ref m if m.starts_with(&SEVENZ_MAGIC) => Some(ArchiveKind::SevenZip),
+        ref m if m.starts_with(&GZIP_MAGIC) => Some(ArchiveKind::Gzip),
+        ref m if m.starts_with(&XZ_MAGIC) => Some(ArchiveKind::Xz),
+        _ => None,
+    };
+
+    Ok(kind)
+}
+
+/// Picks the disk image out of archive member `names`.
+///
+/// It is either the only `.iso` member, or the only member at all.
+fn find_image(names: &[&str]) -> Result<String> {
+    let isos = names
+        .iter()
+        .filter(|n| n.to_ascii_lowercase().ends_with(ISO_EXTENSION))
+        .copied()
+        .collect::<Vec<_>>();
+
+    match (&isos[..], names) {
+        ([name], _) | ([], [name]) => Ok(name.to_string()),
+        _ => Err(Error::from(ErrorKind::InvalidInput)),
+    }
+}
+
+/// Decompresses disk image out of archive at `path` and hands it to `f`.
+///
+/// Nothing is extracted to disk, image is decoded while `f` reads the stream.
+pub fn with_image<F>(path: &Path, kind: ArchiveKind, f: F) -> Result<()>
+where
+    F: FnOnce(StreamImage) -> Result<()>,
+{
+    match kind {
+        ArchiveKind::Zip => with_zip_image(path, f),
+        ArchiveKind::SevenZip => with_sevenz_image(path, f),
+        ArchiveKind::Gzip => {
+            let decoder = MultiGzDecoder::new(BufReader::new(File::open(path)?));
+            f(StreamImage::new(Box::new(decoder), None)?)
+        }
+        ArchiveKind::Xz => {
+            let decoder = XzReader::new(BufReader::new(File::open(path)?), true);
+            f(StreamImage::new(Box::new(decoder), None)?)
+        }
+    }
+}
+
+fn with_zip_image<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(StreamImage) -> Result<()>,
+{
+    let mut zip =
+        ZipArchive::new(File::open(path)?).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    let names = zip
+        .file_names()
+        .filter(|n| !n.ends_with('/'))
+        .collect::<Vec<_>>();
+    let name = find_image(&names)?;
+    let entry = zip
+        .by_name(&name)
+        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+    let size = entry.size();
+    let stream = StreamImage::new(Box::new(entry), Some(size))?;
+    f(stream)
+}
+
+fn with_sevenz_image<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(StreamImage) -> Result<()>,
+{
+    let mut reader = SevenZReader::open(path, Password::empty())
+        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    let names = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|e| !e.is_directory())
+        .map(|e| e.name())
+        .collect::<Vec<_>>();
+    let name = find_image(&names)?;
+
+    let mut f = Some(f);
+    let mut result = Err(Error::from(ErrorKind::NotFound));
+
+    reader
+        .for_each_entries(|entry, data| {
+            if entry.name() != name {
+                // Members of solid archive share one stream, so ones
+                // before the image have to be decoded anyway.
+                std::io::copy(data, &mut sink())?;
+                return Ok(true);
+            }
+
+            let f = f.take().unwrap();
+            result = StreamImage::new(Box::new(data), Some(entry.size())).and_then(f);
+            Ok(false)
+        })
+        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::iso;
+    use std::path::PathBuf;
+
+    fn read_archive(name: &str) -> (String, Option<u64>, usize) {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources");
+        path.push(name);
+
+        let kind = detect(&path).unwrap().unwrap();
+        let mut found = (String::new(), None, 0);
+        with_image(&path, kind, |mut stream| {
+            let serial = iso::read_serial(stream.prefix())?;
+            let mut buffer = Vec::new();
+            stream.read_to_end(&mut buffer)?;
+            found = (serial, stream.size(), buffer.len());
+            Ok(())
+        })
+        .unwrap();
+
+        found
+    }
+
+    #[test]
+    fn test_detect_plain_iso() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        assert_eq!(detect(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_image() {
+        let names = ["readme.txt", "Foo (USA).ISO"];
+        assert_eq!(find_image(&names).unwrap(), "Foo (USA).ISO");
+        assert_eq!(find_image(&["foo.bin"]).unwrap(), "foo.bin");
+        assert!(find_image(&["foo.iso", "bar.iso"]).is_err());
+        assert!(find_image(&["foo.bin", "bar.bin"]).is_err());
+    }
+
+    #[test]
+    fn test_read_zip() {
+        let found = read_archive("testimage.zip");
+        assert_eq!(found, (String::from("SLXS_123.45"), Some(358400), 358400));
+    }
+
+    #[test]
+    fn test_read_sevenz() {
+        let found = read_archive("testimage.7z");
+        assert_eq!(found, (String::from("SLXS_123.45"), Some(358400), 358400));
+    }
+
+    #[test]
+    fn test_read_gzip() {
+        let found = read_archive("testimage.iso.gz");
+        assert_eq!(found, (String::from("SLXS_123.45"), Some(358400), 358400));
+    }
+
+    #[test]
+    fn test_read_xz() {
+        let found = read_archive("testimage.iso.xz");
+        assert_eq!(found, (String::from("SLXS_123.45"), Some(358400), 358400));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::game::source::raw::CD_SYNC_HEADER;
+
+use std::io::prelude::*;
+use std::io::{Cursor, Error, ErrorKind, Result};
+
+use claxon::FlacReader;
+use flate2::read::DeflateDecoder;
+use lzma_rs::decompress::{Options, UnpackedSize};
+
+pub const CD_FRAME_SIZE: usize = 2448;
+pub const CD_MAX_SECTOR_DATA: usize = 2352;
+const CD_MAX_SUBCODE_DATA: usize = 96;
+
+pub const CHD_CODEC_ZLIB: u32 = 0x7a6c6962;
+pub const CHD_CODEC_LZMA: u32 = 0x6c7a6d61;
+pub const CHD_CODEC_FLAC: u32 = 0x666c6163;
+pub const CHD_CODEC_CD_ZLIB: u32 = 0x63647a6c;
+pub const CHD_CODEC_CD_LZMA: u32 = 0x63646c7a;
+pub const CHD_CODEC_CD_FLAC: u32 = 0x6364666c;
+
+// LZMA properties used by chdman: lc=3, lp=0, pb=2.
+const LZMA_PROPERTIES: u8 = 0x5d;
+const FLAC_MAX_BLOCK_SIZE: usize = 2048;
+const FLAC_HEADER_SIZE: usize = 0x2a;
+
+/// Decompresses single CHD hunk encoded with codec `tag` into `dst`.
+pub fn decompress(tag: u32, src: &[u8], dst: &mut [u8], hunk_bytes: usize) -> Result<()> {
+    match tag {
+        CHD_CODEC_ZLIB => inflate(src, dst),
+        CHD_CODEC_LZMA => unlzma(src, dst, hunk_bytes),
+        CHD_CODEC_FLAC => {
+            // First byte tells whether samples were little or big endian.
+            let little_endian = match src.first() {
+                Some(0x4c) => true,
+                Some(0x42) => false,
+                _ => return Err(Error::from(ErrorKind::InvalidData)),
+            };
+            let block_size = flac_block_size(dst.len(), FLAC_MAX_BLOCK_SIZE);
+            unflac(&src[1..], dst, block_size, little_endian)
+        }
+        CHD_CODEC_CD_ZLIB | CHD_CODEC_CD_LZMA | CHD_CODEC_CD_FLAC => {
+            decompress_cd(tag, src, dst, hunk_bytes)
+        }
+        _ => Err(Error::from(ErrorKind::Unsupported)),
+    }
+}
+
+fn decompress_cd(tag: u32, src: &[u8], dst: &mut [u8], hunk_bytes: usize) -> Result<()> {
+    let frames = dst.len() / CD_FRAME_SIZE;
+    let mut sectors = vec![0x00; frames * CD_MAX_SECTOR_DATA];
+    let mut subcode = vec![0x00; frames * CD_MAX_SUBCODE_DATA];
+    let mut ecc_flags = vec![0x00; frames.div_ceil(8)];
+
+    if tag == CHD_CODEC_CD_FLAC {
+        // Audio codec has no ECC flags and stores subcode after FLAC stream
+        // we cannot locate without decoding it, but data tracks don't need it.
+        // CD codec caps blocks at one sector instead of plain FLAC limit.
+        let block_size = flac_block_size(sectors.len(), CD_MAX_SECTOR_DATA);
+        unflac(src, &mut sectors, block_size, false)?;
+    } else {
+        // Hunk starts with ECC flags bitmap and 2 or 3 byte length of base data,
+        // followed by base (sector) and subcode streams.
+        let ecc_bytes = ecc_flags.len();
+        let length_bytes = if dst.len() < 65536 { 2 } else { 3 };
+        let header_bytes = ecc_bytes + length_bytes;
+        if src.len() < header_bytes {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        ecc_flags.copy_from_slice(&src[..ecc_bytes]);
+        let base_length = src[ecc_bytes..header_bytes]
+            .iter()
+            .fold(0, |acc, b| (acc << 8) | *b as usize);
+
+        let base = src
+            .get(header_bytes..header_bytes + base_length)
+            .ok_or(ErrorKind::InvalidData)?;
+        match tag {
+            CHD_CODEC_CD_LZMA => unlzma(base, &mut sectors, hunk_bytes)?,
+            _ => inflate(base, &mut sectors)?,
+        }
+        inflate(&src[header_bytes + base_length..], &mut subcode)?;
+    }
+
+    for frame in 0..frames {
+        let sector = &mut dst[frame * CD_FRAME_SIZE..(frame + 1) * CD_FRAME_SIZE];
+        sector[..CD_MAX_SECTOR_DATA].copy_from_slice(
+            &sectors[frame * CD_MAX_SECTOR_DATA..(frame + 1) * CD_MAX_SECTOR_DATA],
+        );
+        sector[CD_MAX_SECTOR_DATA..].copy_from_slice(
+            &subcode[frame * CD_MAX_SUBCODE_DATA..(frame + 1) * CD_MAX_SUBCODE_DATA],
+        );
+
+        // Stripped sync header is restored, ECC itself is not as only
+        // user data is ever read back.
+        if ecc_flags[frame / 8] & (1 << (frame % 8)) != 0 {
+            sector[..CD_SYNC_HEADER.len()].copy_from_slice(&CD_SYNC_HEADER);
+        }
+    }
+
+    Ok(())
+}
+
+fn inflate(src: &[u8], dst: &mut [u8]) -> Result<()> {
+    // CHD uses raw deflate streams without zlib header.
+    let mut decoder = DeflateDecoder::new(src);
+    decoder.read_exact(dst)
+}
+
+fn lzma_dictionary_size(hunk_bytes: usize) -> u32 {
+    // Mirrors LzmaEncProps_Normalize for level 9 reduced to hunk size.
+    for shift in 11..=30 {
+        if hunk_bytes <= 2 << shift {
+            return 2 << shift;
+        }
+        if hunk_bytes <= 3 << shift {
+            return 3 << shift;
+        }
+    }
+
+    1 << 26
+}
+
+fn unlzma(src: &[u8], dst: &mut [u8], hunk_bytes: usize) -> Result<()> {
+    // CHD stores bare LZMA stream, so properties header is rebuilt here.
+    let mut header = vec![LZMA_PROPERTIES];
+    header.extend_from_slice(&lzma_dictionary_size(hunk_bytes).to_le_bytes());
+
+    let options = Options {
+        unpacked_size: UnpackedSize::UseProvided(Some(dst.len() as u64)),
+        ..Options::default()
+    };
+
+    let mut input = Cursor::new(header).chain(src);
+    let mut output = Vec::with_capacity(dst.len());
+    lzma_rs::lzma_decompress_with_options(&mut input, &mut output, &options)
+        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+    if output.len() != dst.len() {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+
+    dst.copy_from_slice(&output);
+    Ok(())
+}
+
+fn flac_block_size(bytes: usize, max_block_size: usize) -> usize {
+    let mut block_size = bytes / 4;
+    while block_size > max_block_size {
+        block_size /= 2;
+    }
+
+    block_size
+}
+
+fn unflac(src: &[u8], dst: &mut [u8], block_size: usize, little_endian: bool) -> Result<()> {
+    // CHD stores bare FLAC frames of 16 bit stereo at 44.1kHz,
+    // so stream marker and STREAMINFO block are rebuilt here.
+    let mut header = vec![0x00; FLAC_HEADER_SIZE];
+    header[0..4].copy_from_slice(&[0x66, 0x4c, 0x61, 0x43]);
+    header[4] = 0x80;
+    header[7] = 0x22;
+    header[8..10].copy_from_slice(&(block_size as u16).to_be_bytes());
+    header[10..12].copy_from_slice(&(block_size as u16).to_be_bytes());
+    header[18..22].copy_from_slice(&[0x0a, 0xc4, 0x42, 0xf0]);
+
+    let input = Cursor::new(header).chain(src);
+    let mut reader = FlacReader::new(input).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    let mut blocks = reader.blocks();
+    let mut buffer = Vec::new();
+    let mut position = 0;
+
+    while position < dst.len() {
+        let block = match blocks.read_next_or_eof(buffer) {
+            Ok(Some(block)) => block,
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+
+        for sample in 0..block.duration() {
+            for channel in 0..2 {
+                let value = block.sample(channel, sample) as i16;
+                let bytes = match little_endian {
+                    true => value.to_le_bytes(),
+                    false => value.to_be_bytes(),
+                };
+
+                let target = dst
+                    .get_mut(position..position + 2)
+                    .ok_or(ErrorKind::InvalidData)?;
+                target.copy_from_slice(&bytes);
+                position += 2;
+            }
+        }
+
+        buffer = block.into_buffer();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lzma_dictionary_size() {
+        assert_eq!(lzma_dictionary_size(4096), 4096);
+        assert_eq!(lzma_dictionary_size(19584), 24576);
+    }
+
+    #[test]
+    fn test_flac_block_size() {
+        assert_eq!(flac_block_size(4096, FLAC_MAX_BLOCK_SIZE), 1024);
+        assert_eq!(flac_block_size(16384, FLAC_MAX_BLOCK_SIZE), 2048);
+        let cd_block_size = flac_block_size(8 * CD_MAX_SECTOR_DATA, CD_MAX_SECTOR_DATA);
+        assert_eq!(cd_block_size, 2352);
+    }
+
+    #[test]
+    fn test_decompress_unsupported_codec() {
+        let mut dst = [0x00; 16];
+        let result = decompress(0x68756666, &[0x00], &mut dst, 16);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// MSB-first bit reader over CHD map data.
+///
+/// Reading past the end yields zero bits, as in the reference decoder.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    pub fn peek(&self, bits: u8) -> u32 {
+        let mut value = 0;
+        for bit in self.position..self.position + bits as usize {
+            let byte = self.data.get(bit / 8).copied().unwrap_or(0x00);
+            value = (value << 1) | ((byte >> (7 - bit % 8)) & 0x01) as u32;
+        }
+
+        value
+    }
+
+    pub fn remove(&mut self, bits: u8) {
+        self.position += bits as usize;
+    }
+
+    pub fn read(&mut self, bits: u8) -> u32 {
+        let value = self.peek(bits);
+        self.remove(bits);
+        value
+    }
+
+    pub fn overflow(&self) -> bool {
+        self.position > self.data.len() * 8
+    }
+}
+
+/// Canonical Huffman decoder with code lengths imported from RLE encoded tree.
+pub struct Huffman {
+    max_bits: u8,
+    lookup: Vec<(u32, u8)>,
+}
+
+impl Huffman {
+    pub fn import_tree_rle(bits: &mut BitReader, num_codes: usize, max_bits: u8) -> Result<Self> {
+        let num_bits = match max_bits {
+            16.. => 5,
+            8..=15 => 4,
+            _ => 3,
+        };
+
+        let mut lengths = vec![0x00; num_codes];
+        let mut current = 0;
+
+        while current < num_codes {
+            let node_bits = bits.read(num_bits) as u8;
+            if node_bits != 1 {
+                lengths[current] = node_bits;
+                current += 1;
+                continue;
+            }
+
+            // A one is an escape code, two ones are a single one
+            // and anything else is a length followed by its repeat count.
+            let node_bits = bits.read(num_bits) as u8;
+            if node_bits == 1 {
+                lengths[current] = node_bits;
+                current += 1;
+                continue;
+            }
+
+            let repeat = bits.read(num_bits) as usize + 3;
+            if current + repeat > num_codes {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+
+            lengths[current..current + repeat].fill(node_bits);
+            current += repeat;
+        }
+
+        if bits.overflow() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Huffman::from_lengths(&lengths, max_bits)
+    }
+
+    fn from_lengths(lengths: &[u8], max_bits: u8) -> Result<Self> {
+        let mut histogram = [0u32; 33];
+        for length in lengths {
+            if *length > max_bits {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            histogram[*length as usize] += 1;
+        }
+
+        // Longest codes get the lowest code values.
+        let mut start = 0;
+        for length in (1..=32).rev() {
+            let next = (start + histogram[length]) >> 1;
+            if length != 1 && next * 2 != start + histogram[length] {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            histogram[length] = start;
+            start = next;
+        }
+
+        let mut lookup = vec![(0, 0); 1 << max_bits];
+        for (value, length) in lengths.iter().enumerate().filter(|(_, l)| **l > 0) {
+            let code = histogram[*length as usize];
+            histogram[*length as usize] += 1;
+
+            let shift = max_bits - length;
+            let first = (code << shift) as usize;
+            let last = ((code + 1) << shift) as usize;
+            lookup
+                .get_mut(first..last)
+                .ok_or(ErrorKind::InvalidData)?
+                .fill((value as u32, *length));
+        }
+
+        Ok(Huffman { max_bits, lookup })
+    }
+
+    pub fn decode_one(&self, bits: &mut BitReader) -> u32 {
+        let (value, length) = self.lookup[bits.peek(self.max_bits) as usize];
+        bits.remove(length);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitreader_read() {
+        let data = [0b1010_1100, 0b0101_0000];
+        let mut bits = BitReader::new(&data);
+        assert_eq!(bits.read(3), 0b101);
+        assert_eq!(bits.read(9), 0b0_1100_0101);
+        assert_eq!(bits.read(8), 0b0000_0000);
+        assert!(bits.overflow());
+    }
+
+    #[test]
+    fn test_huffman_flat_tree() {
+        // sixteen codes of four bits each, written as raw lengths
+        let mut data = vec![0x44; 8];
+        data.extend_from_slice(&[0x3c]);
+        let mut bits = BitReader::new(&data);
+        let huffman = Huffman::import_tree_rle(&mut bits, 16, 8).unwrap();
+        assert_eq!(huffman.decode_one(&mut bits), 3);
+        assert_eq!(huffman.decode_one(&mut bits), 12);
+    }
+
+    #[test]
+    fn test_huffman_repeated_lengths() {
+        // escape, length 4, repeat 13 + 3 times
+        let data = [0x14, 0xd0];
+        let mut bits = BitReader::new(&data);
+        let huffman = Huffman::import_tree_rle(&mut bits, 16, 8).unwrap();
+        assert_eq!(huffman.decode_one(&mut bits), 0);
+    }
+
+    #[test]
+    fn test_huffman_invalid_tree() {
+        // sixteen codes of two bits each cannot form a prefix code
+        let data = [0x22; 8];
+        let mut bits = BitReader::new(&data);
+        assert!(Huffman::import_tree_rle(&mut bits, 16, 8).is_err());
+    }
+}
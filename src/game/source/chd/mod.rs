@@ -0,0 +1,592 @@
+mod codec;
+mod huffman;
+
+use crate::game::media::MediaType;
+use crate::game::source::chd::codec::CD_FRAME_SIZE;
+use crate::game::source::chd::huffman::{BitReader, Huffman};
+use crate::game::source::ImageSource;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+use regex::Regex;
+
+pub const CHD_MAGIC: [u8; 8] = [0x4d, 0x43, 0x6f, 0x6d, 0x70, 0x72, 0x48, 0x44];
+const CHD_V5_HEADER_SIZE: usize = 124;
+const CHD_V5_MAP_HEADER_SIZE: usize = 16;
+const CHD_V5_VERSION: u32 = 5;
+const CHD_METADATA_HEADER_SIZE: usize = 16;
+const CHD_MAX_SELF_REFERENCES: usize = 16;
+// chdman never makes hunks larger than 1 MiB.
+const CHD_MAX_HUNK_BYTES: u64 = 1024 * 1024;
+// Dual-layer DVD holds 8.5 GB, anything past twice that is no PS2 image.
+const CHD_MAX_LOGICAL_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+// Real images have a handful of metadata entries, more means a loop.
+const CHD_MAX_METADATA_ENTRIES: usize = 1024;
+const CDROM_TRACK_METADATA_TAG: u32 = 0x43485452;
+const CDROM_TRACK_METADATA2_TAG: u32 = 0x43485432;
+const CD_TRACK_PADDING: u64 = 4;
+const SECTOR_SIZE: u64 = 2048;
+
+// Hunk compression types used by V5 map.
+const COMPRESSION_TYPE_3: u32 = 3;
+const COMPRESSION_NONE: u32 = 4;
+const COMPRESSION_SELF: u32 = 5;
+const COMPRESSION_PARENT: u32 = 6;
+const COMPRESSION_RLE_SMALL: u32 = 7;
+const COMPRESSION_RLE_LARGE: u32 = 8;
+const COMPRESSION_SELF_0: u32 = 9;
+const COMPRESSION_SELF_1: u32 = 10;
+const COMPRESSION_PARENT_SELF: u32 = 11;
+const COMPRESSION_PARENT_0: u32 = 12;
+const COMPRESSION_PARENT_1: u32 = 13;
+
+enum Hunk {
+    Compressed {
+        codec: usize,
+        offset: u64,
+        length: u64,
+    },
+    Uncompressed(u64),
+    Duplicate(u64),
+    Parent,
+    Zero,
+}
+
+enum Layout {
+    Dvd,
+    Cd {
+        first_frame: u64,
+        data_offset: usize,
+    },
+}
+
+/// MAME CHD v5 image, as created by `chdman createdvd` or `chdman createcd`.
+///
+/// DVD images are read as is, while for CD images user data is extracted
+/// from raw frames of the first data track.
+pub struct ChdImage {
+    file: File,
+    hunk_bytes: u64,
+    codecs: [u32; 4],
+    map: Vec<Hunk>,
+    layout: Layout,
+    size: u64,
+    position: u64,
+    cached_hunk: Option<u64>,
+    cache: Vec<u8>,
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+impl ChdImage {
+    pub fn new(mut file: File) -> Result<Self> {
+        let mut header = [0x00; CHD_V5_HEADER_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        // Only fields used here are listed, all big endian:
+        // 8 bytes magic, 4 bytes header length, 4 bytes version,
+        // 4x4 bytes compressors, 8 bytes logical size, 8 bytes map offset,
+        // 8 bytes metadata offset, 4 bytes hunk size, 4 bytes unit size.
+        if header[0..8] != CHD_MAGIC || be_u64(&header[12..16]) != CHD_V5_VERSION as u64 {
+            return Err(Error::from(ErrorKind::Unsupported));
+        }
+
+        let mut codecs = [0; 4];
+        for (num, codec) in codecs.iter_mut().enumerate() {
+            *codec = be_u64(&header[16 + num * 4..20 + num * 4]) as u32;
+        }
+
+        let logical_bytes = be_u64(&header[32..40]);
+        let map_offset = be_u64(&header[40..48]);
+        let meta_offset = be_u64(&header[48..56]);
+        let hunk_bytes = be_u64(&header[56..60]);
+        let unit_bytes = be_u64(&header[60..64]);
+
+        if hunk_bytes == 0
+            || hunk_bytes > CHD_MAX_HUNK_BYTES
+            || unit_bytes == 0
+            || hunk_bytes % unit_bytes != 0
+            || logical_bytes > CHD_MAX_LOGICAL_BYTES
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // Sizes in header are checked against file before anything is allocated.
+        let file_len = file.metadata()?.len();
+        let hunk_count = logical_bytes.div_ceil(hunk_bytes);
+        let map = match codecs[0] {
+            0 => read_raw_map(&mut file, file_len, map_offset, hunk_count, hunk_bytes)?,
+            _ => read_compressed_map(&mut file, file_len, map_offset, hunk_count, hunk_bytes)?,
+        };
+
+        let (layout, size) = match read_cd_track(&mut file, file_len, meta_offset)? {
+            Some((first_frame, frames, data_offset)) => {
+                let layout = Layout::Cd {
+                    first_frame,
+                    data_offset,
+                };
+                (layout, frames * SECTOR_SIZE)
+            }
+            None => (Layout::Dvd, logical_bytes),
+        };
+
+        Ok(ChdImage {
+            file,
+            hunk_bytes,
+            codecs,
+            map,
+            layout,
+            size,
+            position: 0,
+            cached_hunk: None,
+            cache: vec![0x00; hunk_bytes as usize],
+        })
+    }
+
+    fn load_hunk(&mut self, hunk: u64) -> Result<()> {
+        if self.cached_hunk == Some(hunk) {
+            return Ok(());
+        }
+
+        // Follow references to earlier hunks with identical contents.
+        let mut target = hunk;
+        for _ in 0..CHD_MAX_SELF_REFERENCES {
+            let entry = self
+                .map
+                .get(target as usize)
+                .ok_or(ErrorKind::InvalidData)?;
+            match *entry {
+                Hunk::Duplicate(other) => target = other,
+                _ => break,
+            }
+        }
+
+        match self
+            .map
+            .get(target as usize)
+            .ok_or(ErrorKind::InvalidData)?
+        {
+            Hunk::Compressed {
+                codec,
+                offset,
+                length,
+            } => {
+                let mut compressed = vec![0x00; *length as usize];
+                self.file.seek(SeekFrom::Start(*offset))?;
+                self.file.read_exact(&mut compressed)?;
+                let tag = self.codecs[*codec];
+                codec::decompress(tag, &compressed, &mut self.cache, self.hunk_bytes as usize)?;
+            }
+            Hunk::Uncompressed(offset) => {
+                self.file.seek(SeekFrom::Start(*offset))?;
+                self.file.read_exact(&mut self.cache)?;
+            }
+            Hunk::Zero => self.cache.fill(0x00),
+            // Differential images need their parent CHD which is not supported.
+            Hunk::Parent => return Err(Error::from(ErrorKind::Unsupported)),
+            Hunk::Duplicate(_) => return Err(Error::from(ErrorKind::InvalidData)),
+        }
+
+        self.cached_hunk = Some(hunk);
+        Ok(())
+    }
+}
+
+/// Tells whether `length` bytes at `offset` lie within file of `file_len` bytes.
+fn within(file_len: u64, offset: u64, length: u64) -> bool {
+    offset
+        .checked_add(length)
+        .is_some_and(|end| end <= file_len)
+}
+
+fn read_raw_map(
+    file: &mut File,
+    file_len: u64,
+    offset: u64,
+    hunk_count: u64,
+    hunk_bytes: u64,
+) -> Result<Vec<Hunk>> {
+    let map_bytes = hunk_count * 4;
+    if !within(file_len, offset, map_bytes) {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+
+    // Uncompressed V5 map stores hunk position in file in units of hunk size.
+    let mut raw = vec![0x00; map_bytes as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut raw)?;
+
+    raw.chunks_exact(4)
+        .map(|e| match be_u64(e) {
+            0 => Ok(Hunk::Zero),
+            n if within(file_len, n * hunk_bytes, hunk_bytes) => {
+                Ok(Hunk::Uncompressed(n * hunk_bytes))
+            }
+            _ => Err(Error::from(ErrorKind::InvalidData)),
+        })
+        .collect()
+}
+
+fn read_compressed_map(
+    file: &mut File,
+    file_len: u64,
+    offset: u64,
+    hunk_count: u64,
+    hunk_bytes: u64,
+) -> Result<Vec<Hunk>> {
+    // Map header: 4 bytes compressed size, 6 bytes offset of first hunk,
+    // 2 bytes CRC, then bit widths of length, self and parent fields.
+    let mut header = [0x00; CHD_V5_MAP_HEADER_SIZE];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+
+    let map_bytes = be_u64(&header[0..4]);
+    let mut current_offset = be_u64(&header[4..10]);
+    let (length_bits, self_bits, parent_bits) = (header[12], header[13], header[14]);
+
+    if !within(
+        file_len,
+        offset.saturating_add(CHD_V5_MAP_HEADER_SIZE as u64),
+        map_bytes,
+    ) {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+
+    let mut compressed = vec![0x00; map_bytes as usize];
+    file.read_exact(&mut compressed)?;
+    let mut bits = BitReader::new(&compressed);
+
+    // Compression types come first, Huffman coded with run length escapes.
+    let decoder = Huffman::import_tree_rle(&mut bits, 16, 8)?;
+    let mut types = Vec::new();
+    let mut last_type = 0;
+    let mut repeat = 0;
+
+    for _ in 0..hunk_count {
+        // Garbage read past end of map would go on for every hunk.
+        if bits.overflow() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        if repeat > 0 {
+            repeat -= 1;
+            types.push(last_type);
+            continue;
+        }
+
+        match decoder.decode_one(&mut bits) {
+            COMPRESSION_RLE_SMALL => repeat = 2 + decoder.decode_one(&mut bits),
+            COMPRESSION_RLE_LARGE => {
+                repeat = 2 + 16 + (decoder.decode_one(&mut bits) << 4);
+                repeat += decoder.decode_one(&mut bits);
+            }
+            value => last_type = value,
+        }
+        types.push(last_type);
+    }
+
+    // Then lengths, offsets and CRCs of each hunk.
+    let mut map = Vec::with_capacity(types.len());
+    let mut last_self = 0;
+
+    for hunk_type in types {
+        let hunk = match hunk_type {
+            0..=COMPRESSION_TYPE_3 => {
+                let length = bits.read(length_bits) as u64;
+                bits.remove(16);
+                if !within(file_len, current_offset, length) {
+                    return Err(Error::from(ErrorKind::InvalidData));
+                }
+                let hunk = Hunk::Compressed {
+                    codec: hunk_type as usize,
+                    offset: current_offset,
+                    length,
+                };
+                current_offset += length;
+                hunk
+            }
+            COMPRESSION_NONE => {
+                bits.remove(16);
+                if !within(file_len, current_offset, hunk_bytes) {
+                    return Err(Error::from(ErrorKind::InvalidData));
+                }
+                let hunk = Hunk::Uncompressed(current_offset);
+                current_offset += hunk_bytes;
+                hunk
+            }
+            COMPRESSION_SELF => {
+                last_self = bits.read(self_bits) as u64;
+                Hunk::Duplicate(last_self)
+            }
+            COMPRESSION_SELF_0 => Hunk::Duplicate(last_self),
+            COMPRESSION_SELF_1 => {
+                last_self += 1;
+                Hunk::Duplicate(last_self)
+            }
+            COMPRESSION_PARENT => {
+                bits.remove(parent_bits);
+                Hunk::Parent
+            }
+            COMPRESSION_PARENT_SELF | COMPRESSION_PARENT_0 | COMPRESSION_PARENT_1 => Hunk::Parent,
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+
+        map.push(hunk);
+    }
+
+    if bits.overflow() {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+
+    Ok(map)
+}
+
+fn read_cd_track(
+    file: &mut File,
+    file_len: u64,
+    mut offset: u64,
+) -> Result<Option<(u64, u64, usize)>> {
+    // CD track metadata is text like:
+    // TRACK:1 TYPE:MODE2_RAW SUBTYPE:NONE FRAMES:1234 PREGAP:0 PGTYPE:MODE1 ...
+    let re = Regex::new(
+        "TRACK:(\\d+) TYPE:(\\S+) SUBTYPE:\\S+ FRAMES:(\\d+)(?: PREGAP:(\\d+) PGTYPE:(\\S+))?",
+    )
+    .unwrap();
+    let mut first_frame = 0;
+    let mut is_cd = false;
+    let mut entries = 0;
+
+    while offset != 0 {
+        entries += 1;
+        if entries > CHD_MAX_METADATA_ENTRIES {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // Metadata entry: 4 bytes tag, 1 byte flags, 3 bytes length
+        // and 8 bytes offset of next entry, followed by data.
+        let mut header = [0x00; CHD_METADATA_HEADER_SIZE];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+
+        let tag = be_u64(&header[0..4]) as u32;
+        let length = be_u64(&header[5..8]);
+        let data_start = offset + CHD_METADATA_HEADER_SIZE as u64;
+        offset = be_u64(&header[8..16]);
+
+        if tag != CDROM_TRACK_METADATA_TAG && tag != CDROM_TRACK_METADATA2_TAG {
+            continue;
+        }
+
+        is_cd = true;
+        if !within(file_len, data_start, length) {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let mut data = vec![0x00; length as usize];
+        file.read_exact(&mut data)?;
+        let text = String::from_utf8_lossy(&data);
+        let track = re.captures(&text).ok_or(ErrorKind::InvalidData)?;
+
+        let frames = track[3]
+            .parse::<u64>()
+            .map_err(|_| ErrorKind::InvalidData)?;
+        let pregap = track
+            .get(4)
+            .map_or(0, |p| p.as_str().parse::<u64>().unwrap_or(0));
+        let pregap_in_data = track.get(5).is_some_and(|t| t.as_str().starts_with('V'));
+
+        let data_offset = match &track[2] {
+            "MODE1" | "MODE2_FORM1" => Some(0),
+            "MODE2" | "MODE2_FORM_MIX" => Some(8),
+            "MODE1_RAW" => Some(16),
+            "MODE2_RAW" => Some(24),
+            _ => None,
+        };
+
+        if let Some(data_offset) = data_offset {
+            // Pregap marked as present in data precedes actual track contents.
+            let skipped = if pregap_in_data { pregap } else { 0 };
+            return Ok(Some((
+                first_frame + skipped,
+                frames - skipped.min(frames),
+                data_offset,
+            )));
+        }
+
+        // Each track is padded up to multiple of four frames.
+        first_frame += frames.div_ceil(CD_TRACK_PADDING) * CD_TRACK_PADDING;
+    }
+
+    match is_cd {
+        // Audio only discs have nothing to read user data from.
+        true => Err(Error::from(ErrorKind::InvalidData)),
+        false => Ok(None),
+    }
+}
+
+impl Read for ChdImage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let remaining = (self.size - self.position) as usize;
+        let (logical, available) = match self.layout {
+            Layout::Dvd => (self.position, remaining),
+            Layout::Cd {
+                first_frame,
+                data_offset,
+            } => {
+                let frame = first_frame + self.position / SECTOR_SIZE;
+                let offset = (self.position % SECTOR_SIZE) as usize;
+                let logical = frame * CD_FRAME_SIZE as u64 + (data_offset + offset) as u64;
+                (logical, (SECTOR_SIZE as usize - offset).min(remaining))
+            }
+        };
+
+        let hunk = logical / self.hunk_bytes;
+        let offset = (logical % self.hunk_bytes) as usize;
+        self.load_hunk(hunk)?;
+
+        let count = available
+            .min(self.hunk_bytes as usize - offset)
+            .min(buf.len());
+        buf[..count].copy_from_slice(&self.cache[offset..offset + count]);
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for ChdImage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.size as i64 + p,
+            SeekFrom::Current(p) => self.position as i64 + p,
+        };
+
+        if position < 0 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+impl ImageSource for ChdImage {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn media(&self) -> MediaType {
+        match self.layout {
+            Layout::Cd { .. } => MediaType::Cd,
+            Layout::Dvd => MediaType::Dvd,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use std::path::PathBuf;
+
+    fn read_image(name: &str) -> Vec<u8> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources");
+        path.push(name);
+        let mut buffer = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut buffer).unwrap();
+        buffer
+    }
+
+    fn open_image(name: &str) -> ChdImage {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources");
+        path.push(name);
+        ChdImage::new(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_chd_dvd_matches_plain() {
+        let expected = read_image("testimage.iso");
+        let mut decoded = Vec::new();
+        let mut image = open_image("testimage.chd");
+        image.read_to_end(&mut decoded).unwrap();
+        assert_eq!(image.size(), 358400);
+        assert!(expected == decoded);
+    }
+
+    #[test]
+    fn test_chd_cd_matches_plain() {
+        let expected = read_image("testimage.iso");
+        let mut decoded = Vec::new();
+        let mut image = open_image("testimage.cd.chd");
+        image.read_to_end(&mut decoded).unwrap();
+        assert_eq!(image.size(), 358400);
+        assert_eq!(image.media(), MediaType::Cd);
+        assert!(expected == decoded);
+    }
+
+    #[test]
+    fn test_chd_read_across_hunks() {
+        let expected = read_image("testimage.iso");
+        let mut image = open_image("testimage.chd");
+        let mut buffer = vec![0x00; 6144];
+        image.seek(SeekFrom::Start(3072)).unwrap();
+        image.read_exact(&mut buffer).unwrap();
+        assert!(buffer == expected[3072..9216]);
+    }
+
+    fn open_corrupt(name: &str, data: &[u8]) -> Result<ChdImage> {
+        let dir = TestDir::new(name);
+        let path = dir.join("corrupt.chd");
+        std::fs::write(&path, data).unwrap();
+        ChdImage::new(File::open(&path).unwrap())
+    }
+
+    #[test]
+    fn test_chd_corrupt_sizes() {
+        let image = read_image("testimage.chd");
+
+        let mut huge_hunk = image.clone();
+        huge_hunk[56..60].copy_from_slice(&0x8000_0000u32.to_be_bytes());
+        huge_hunk[60..64].copy_from_slice(&0x8000_0000u32.to_be_bytes());
+        let error = open_corrupt("chd_hunk", &huge_hunk).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let mut huge_map = image.clone();
+        let map_offset = be_u64(&image[40..48]) as usize;
+        huge_map[map_offset..map_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let error = open_corrupt("chd_map", &huge_map).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_chd_metadata_loop() {
+        // Metadata entry at end of file points back at itself.
+        let mut image = read_image("testimage.chd");
+        let entry = image.len() as u64;
+        image[48..56].copy_from_slice(&entry.to_be_bytes());
+        image.extend_from_slice(b"XXXX\x00\x00\x00\x00");
+        image.extend_from_slice(&entry.to_be_bytes());
+
+        let error = open_corrupt("chd_loop", &image).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_chd_invalid_header() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let image = ChdImage::new(File::open(path).unwrap());
+        assert!(image.is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::game::source::ImageSource;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::path::Path;
+
+/// Image made of `ul.` chunk files, read as if they were put back together.
+pub struct ChunkedImage {
+    chunks: Vec<(File, u64)>,
+    size: u64,
+    position: u64,
+}
+
+impl ChunkedImage {
+    /// Opens chunk files at `paths`, which have to be in order.
+    pub fn open(paths: &[&Path]) -> Result<Self> {
+        let mut chunks = Vec::new();
+        for path in paths {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            chunks.push((file, size));
+        }
+
+        let size = chunks.iter().map(|(_, s)| s).sum();
+        Ok(ChunkedImage {
+            chunks,
+            size,
+            position: 0,
+        })
+    }
+}
+
+impl Read for ChunkedImage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Reads carry on into next chunk, as ISO9660 reader expects
+        // whole sectors even when one is split between two files.
+        let mut filled = 0;
+        let mut start = 0;
+
+        for (file, size) in self.chunks.iter_mut() {
+            let end = start + *size;
+            if filled < buf.len() && self.position < end {
+                let offset = self.position - start;
+                let count = (buf.len() - filled).min((end - self.position) as usize);
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut buf[filled..filled + count])?;
+                self.position += count as u64;
+                filled += count;
+            }
+
+            start = end;
+        }
+
+        Ok(filled)
+    }
+}
+
+impl Seek for ChunkedImage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.size as i64 + p,
+            SeekFrom::Current(p) => self.position as i64 + p,
+        };
+
+        if position < 0 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+impl ImageSource for ChunkedImage {
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::systemcnf::SystemCnf;
+    use crate::testutil::TestDir;
+    use std::fs::{read, write};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_chunked_read_system_cnf() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let data = read(path).unwrap();
+
+        // Split right inside SYSTEM.CNF, which starts at sector 24.
+        let dir = TestDir::new("chunked");
+        let first = dir.join("ul.chunked_test.00");
+        let second = dir.join("ul.chunked_test.01");
+        write(&first, &data[..49160]).unwrap();
+        write(&second, &data[49160..]).unwrap();
+
+        let mut image = ChunkedImage::open(&[first.as_path(), second.as_path()]).unwrap();
+        assert_eq!(image.size(), 358400);
+        let cnf = SystemCnf::read(&mut image).unwrap();
+        assert_eq!(cnf.serial().unwrap(), "SLXS_123.45");
+    }
+}
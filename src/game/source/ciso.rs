@@ -0,0 +1,235 @@
+use crate::game::source::{lz4, ImageSource};
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+use flate2::read::DeflateDecoder;
+
+pub const CISO_MAGIC: [u8; 4] = [0x43, 0x49, 0x53, 0x4f];
+pub const ZISO_MAGIC: [u8; 4] = [0x5a, 0x49, 0x53, 0x4f];
+const CISO_HEADER_SIZE: usize = 24;
+const CISO_PLAIN_BLOCK: u32 = 0x80000000;
+const CISO_MAX_BLOCK_SIZE: u32 = 0x100000;
+
+enum Codec {
+    Deflate,
+    Lz4,
+}
+
+/// Block compressed image in CISO (deflate) or ZISO (LZ4) container.
+///
+/// Both formats share the header and block index layout and only differ
+/// in how compressed blocks are encoded.
+pub struct CisoImage {
+    file: File,
+    codec: Codec,
+    size: u64,
+    block_size: u32,
+    align: u8,
+    index: Vec<u32>,
+    position: u64,
+    cached_block: Option<u64>,
+    cache: Vec<u8>,
+}
+
+impl CisoImage {
+    pub fn new(mut file: File) -> Result<Self> {
+        // CISO header layout:
+        // 4 bytes magic, 4 bytes header size, 8 bytes uncompressed size,
+        // 4 bytes block size, 1 byte version, 1 byte index alignment
+        // and 2 bytes reserved, all little endian.
+        let mut header = [0x00; CISO_HEADER_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        let codec = match header[0..4].try_into().unwrap() {
+            CISO_MAGIC => Codec::Deflate,
+            ZISO_MAGIC => Codec::Lz4,
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+
+        let size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let block_size = u32::from_le_bytes(header[16..20].try_into().unwrap());
+        let align = header[21];
+
+        if block_size == 0 || block_size > CISO_MAX_BLOCK_SIZE || align > 31 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // One index entry per block plus one marking the end of the last block.
+        // Header fields are untrusted, so index has to fit in the file.
+        let available = file
+            .metadata()?
+            .len()
+            .saturating_sub(CISO_HEADER_SIZE as u64);
+        let index_size = size
+            .div_ceil(block_size as u64)
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(4))
+            .filter(|s| *s <= available)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+
+        let mut raw_index = vec![0x00; index_size as usize];
+        file.read_exact(&mut raw_index)?;
+
+        let index = raw_index
+            .chunks_exact(4)
+            .map(|e| u32::from_le_bytes(e.try_into().unwrap()))
+            .collect::<Vec<u32>>();
+
+        Ok(CisoImage {
+            file,
+            codec,
+            size,
+            block_size,
+            align,
+            index,
+            position: 0,
+            cached_block: None,
+            cache: vec![0x00; block_size as usize],
+        })
+    }
+
+    fn load_block(&mut self, block: u64) -> Result<()> {
+        if self.cached_block == Some(block) {
+            return Ok(());
+        }
+
+        let entry = self.index[block as usize];
+        let next = self.index[block as usize + 1];
+        let start = ((entry & !CISO_PLAIN_BLOCK) as u64) << self.align;
+        let end = ((next & !CISO_PLAIN_BLOCK) as u64) << self.align;
+
+        if end < start {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        self.file.seek(SeekFrom::Start(start))?;
+        if entry & CISO_PLAIN_BLOCK != 0 {
+            self.file.read_exact(&mut self.cache)?;
+        } else {
+            let mut compressed = (&mut self.file).take(end - start);
+            match self.codec {
+                Codec::Deflate => {
+                    // Compressed blocks are raw deflate streams without zlib header.
+                    let mut decoder = DeflateDecoder::new(compressed);
+                    decoder.read_exact(&mut self.cache)?;
+                }
+                Codec::Lz4 => {
+                    let mut buffer = Vec::new();
+                    compressed.read_to_end(&mut buffer)?;
+                    lz4::decompress_block(&buffer, &mut self.cache)?;
+                }
+            }
+        }
+
+        self.cached_block = Some(block);
+        Ok(())
+    }
+}
+
+impl Read for CisoImage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let block = self.position / self.block_size as u64;
+        let offset = (self.position % self.block_size as u64) as usize;
+        self.load_block(block)?;
+
+        let remaining = (self.size - self.position) as usize;
+        let available = (self.block_size as usize - offset).min(remaining);
+        let count = available.min(buf.len());
+        buf[..count].copy_from_slice(&self.cache[offset..offset + count]);
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for CisoImage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.size as i64 + p,
+            SeekFrom::Current(p) => self.position as i64 + p,
+        };
+
+        if position < 0 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+impl ImageSource for CisoImage {
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_ciso_read_system_cnf() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let mut image = CisoImage::new(File::open(path).unwrap()).unwrap();
+        let mut buffer = [0x00; 29];
+        image.seek(SeekFrom::Start(49152)).unwrap();
+        image.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"BOOT2 = cdrom0:\\SLXS_123.45;1");
+    }
+
+    #[test]
+    fn test_ziso_read_system_cnf() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.zso");
+        let mut image = CisoImage::new(File::open(path).unwrap()).unwrap();
+        let mut buffer = [0x00; 29];
+        image.seek(SeekFrom::Start(49152)).unwrap();
+        image.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"BOOT2 = cdrom0:\\SLXS_123.45;1");
+    }
+
+    #[test]
+    fn test_ciso_read_past_end() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let mut image = CisoImage::new(File::open(path).unwrap()).unwrap();
+        let mut buffer = [0x00; 16];
+        image.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(image.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_ciso_index_larger_than_file() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let mut data = std::fs::read(path).unwrap();
+        data[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[16..20].copy_from_slice(&1u32.to_le_bytes());
+
+        let dir = TestDir::new("ciso_index");
+        let csopath = dir.join("huge.cso");
+        std::fs::write(&csopath, data).unwrap();
+        let image = CisoImage::new(File::open(csopath).unwrap());
+        assert_eq!(image.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_ciso_invalid_header() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let image = CisoImage::new(File::open(path).unwrap());
+        assert!(image.is_err());
+    }
+}
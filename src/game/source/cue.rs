@@ -0,0 +1,130 @@
+use crate::game::source::raw::{RawImage, TrackMode};
+
+use std::fs::{read_to_string, File};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+const CD_FRAMES_PER_SECOND: u64 = 75;
+const CD_SECONDS_PER_MINUTE: u64 = 60;
+
+#[derive(Debug, PartialEq)]
+pub struct CueTrack {
+    pub path: PathBuf,
+    pub mode: TrackMode,
+    pub first_sector: u64,
+    pub sectors: Option<u64>,
+}
+
+fn parse_msf(msf: &str) -> Option<u64> {
+    let parts = msf
+        .split(':')
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    match parts[..] {
+        [m, s, f] => Some((m * CD_SECONDS_PER_MINUTE + s) * CD_FRAMES_PER_SECOND + f),
+        _ => None,
+    }
+}
+
+/// Finds first data track in cue sheet `contents` with files relative to `dir`.
+pub fn parse(contents: &str, dir: &Path) -> Result<CueTrack> {
+    let file_re = Regex::new("^FILE\\s+(?:\"(.+)\"|(\\S+))\\s+\\S+$").unwrap();
+    let track_re = Regex::new("^TRACK\\s+\\d+\\s+(\\S+)$").unwrap();
+    let index_re = Regex::new("^INDEX\\s+01\\s+(\\d+:\\d+:\\d+)$").unwrap();
+
+    let mut file: Option<PathBuf> = None;
+    let mut mode: Option<TrackMode> = None;
+    let mut found: Option<CueTrack> = None;
+
+    for line in contents.lines().map(|l| l.trim()) {
+        if let Some(caps) = file_re.captures(line) {
+            if found.is_some() {
+                // Data track runs until the end of its own file.
+                break;
+            }
+            let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            file = Some(dir.join(name));
+        } else if let Some(caps) = track_re.captures(line) {
+            mode = TrackMode::from_cue(&caps[1]);
+        } else if let Some(caps) = index_re.captures(line) {
+            let start = parse_msf(&caps[1]).ok_or(ErrorKind::InvalidData)?;
+
+            if let Some(track) = found.as_mut() {
+                // Next track in the same file ends the data track.
+                track.sectors = Some(start - track.first_sector.min(start));
+                break;
+            }
+
+            if let Some(mode) = mode {
+                let path = file.clone().ok_or(ErrorKind::InvalidData)?;
+                found = Some(CueTrack {
+                    path,
+                    mode,
+                    first_sector: start,
+                    sectors: None,
+                });
+            }
+        }
+    }
+
+    found.ok_or_else(|| Error::from(ErrorKind::NotFound))
+}
+
+/// Opens first data track described by cue sheet at `path`.
+pub fn open(path: &Path) -> Result<RawImage> {
+    let contents = read_to_string(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let track = parse(&contents, dir)?;
+    let file = File::open(&track.path)?;
+
+    RawImage::new(file, track.mode, track.first_sector, track.sectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_track() {
+        let cue = "FILE \"Foo (USA).bin\" BINARY\n  TRACK 01 MODE2/2352\n    INDEX 01 00:00:00\n";
+        let track = parse(cue, Path::new("games")).unwrap();
+        assert_eq!(track.path, Path::new("games/Foo (USA).bin"));
+        assert_eq!(track.mode, TrackMode::Mode2Raw);
+        assert_eq!(track.first_sector, 0);
+        assert_eq!(track.sectors, None);
+    }
+
+    #[test]
+    fn test_parse_audio_before_data() {
+        let cue = concat!(
+            "FILE foo.bin BINARY\r\n",
+            "  TRACK 01 AUDIO\r\n",
+            "    INDEX 01 00:00:00\r\n",
+            "  TRACK 02 MODE1/2352\r\n",
+            "    INDEX 00 00:02:00\r\n",
+            "    INDEX 01 00:04:00\r\n",
+            "  TRACK 03 AUDIO\r\n",
+            "    INDEX 01 01:00:00\r\n",
+        );
+        let track = parse(cue, Path::new("")).unwrap();
+        assert_eq!(track.mode, TrackMode::Mode1Raw);
+        assert_eq!(track.first_sector, 300);
+        assert_eq!(track.sectors, Some(4200));
+    }
+
+    #[test]
+    fn test_parse_audio_only() {
+        let cue = "FILE foo.bin BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(parse(cue, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_open_cue() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cue");
+        assert!(open(&path).is_ok());
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{Error, ErrorKind, Result};
+
+const LZ4_MIN_MATCH: usize = 4;
+const LZ4_LENGTH_MASK: usize = 0x0f;
+
+fn read_length(src: &[u8], pos: &mut usize, nibble: usize) -> Result<usize> {
+    let mut length = nibble;
+    if nibble == LZ4_LENGTH_MASK {
+        loop {
+            let byte = *src.get(*pos).ok_or(ErrorKind::InvalidData)?;
+            *pos += 1;
+            length += byte as usize;
+            if byte != 0xff {
+                break;
+            }
+        }
+    }
+
+    Ok(length)
+}
+
+/// Decodes raw LZ4 block from `src` until `dst` is filled.
+///
+/// ZSO blocks are padded up to the index alignment, so anything left in
+/// `src` once the output is complete is ignored.
+pub fn decompress_block(src: &[u8], dst: &mut [u8]) -> Result<()> {
+    let mut ipos = 0;
+    let mut opos = 0;
+
+    while opos < dst.len() {
+        let token = *src.get(ipos).ok_or(ErrorKind::InvalidData)? as usize;
+        ipos += 1;
+
+        let literals = read_length(src, &mut ipos, token >> 4)?;
+        if ipos + literals > src.len() || opos + literals > dst.len() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        dst[opos..opos + literals].copy_from_slice(&src[ipos..ipos + literals]);
+        ipos += literals;
+        opos += literals;
+
+        if opos == dst.len() {
+            break;
+        }
+
+        if ipos + 2 > src.len() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let offset = src[ipos] as usize | (src[ipos + 1] as usize) << 8;
+        ipos += 2;
+
+        let length = read_length(src, &mut ipos, token & LZ4_LENGTH_MASK)? + LZ4_MIN_MATCH;
+        if offset == 0 || offset > opos || opos + length > dst.len() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // Matches may overlap the bytes they produce, so copy byte by byte.
+        for _ in 0..length {
+            dst[opos] = dst[opos - offset];
+            opos += 1;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_literals_only() {
+        let src = [0x30, 0x66, 0x6f, 0x6f];
+        let mut dst = [0x00; 3];
+        decompress_block(&src, &mut dst).unwrap();
+        assert_eq!(&dst, b"foo");
+    }
+
+    #[test]
+    fn test_decompress_overlapping_match() {
+        // one literal `a` followed by match of length 7 at offset 1
+        let src = [0x13, 0x61, 0x01, 0x00];
+        let mut dst = [0x00; 8];
+        decompress_block(&src, &mut dst).unwrap();
+        assert_eq!(&dst, b"aaaaaaaa");
+    }
+
+    #[test]
+    fn test_decompress_ignores_padding() {
+        let src = [0x30, 0x66, 0x6f, 0x6f, 0x00, 0x00];
+        let mut dst = [0x00; 3];
+        decompress_block(&src, &mut dst).unwrap();
+        assert_eq!(&dst, b"foo");
+    }
+
+    #[test]
+    fn test_decompress_invalid_offset() {
+        let src = [0x10, 0x61, 0x05, 0x00];
+        let mut dst = [0x00; 8];
+        assert!(decompress_block(&src, &mut dst).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+pub mod archive;
+mod chd;
+pub mod chunked;
+mod ciso;
+mod cue;
+mod lz4;
+mod raw;
+pub mod stream;
+
+use crate::game::media::MediaType;
+use crate::game::source::chd::ChdImage;
+use crate::game::source::ciso::CisoImage;
+use crate::game::source::raw::RawImage;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::path::Path;
+
+const MAGIC_SIZE: usize = 16;
+const CUE_EXTENSION: &str = "cue";
+
+/// Seekable stream of 2048-byte user data sectors backing a disk image.
+///
+/// Compressed images decode their blocks on the fly, so both `SYSTEM.CNF`
+/// lookup and chunk writing see a plain ISO regardless of the container.
+pub trait ImageSource: Read + Seek {
+    /// Size of the uncompressed image in bytes.
+    fn size(&self) -> u64;
+
+    /// Disc type the image was dumped from.
+    fn media(&self) -> MediaType {
+        MediaType::Dvd
+    }
+}
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Image opened to look at its contents, which for archives is only
+/// the start kept in memory.
+pub struct ImageStart<'a> {
+    pub image: Box<dyn ReadSeek + 'a>,
+    pub media: MediaType,
+    /// Size of whole image, unless archive does not tell it.
+    pub size: Option<u64>,
+    /// Whether `image` can be read to its end.
+    pub whole: bool,
+}
+
+pub struct PlainImage {
+    file: File,
+    size: u64,
+}
+
+impl PlainImage {
+    pub fn new(file: File) -> Result<Self> {
+        let size = file.metadata()?.len();
+        Ok(PlainImage { file, size })
+    }
+}
+
+impl Read for PlainImage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for PlainImage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl ImageSource for PlainImage {
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Opens disk image at `path`, picking a reader based on its magic bytes.
+pub fn open(path: &Path) -> Result<Box<dyn ImageSource>> {
+    // Cue sheets are plain text, so these are told apart by extension.
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if extension.eq_ignore_ascii_case(CUE_EXTENSION) {
+        return Ok(Box::new(cue::open(path)?));
+    }
+
+    let mut file = File::open(path)?;
+    let mut magic = [0x00; MAGIC_SIZE];
+    let read = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if read < MAGIC_SIZE {
+        return Ok(Box::new(PlainImage::new(file)?));
+    }
+
+    if magic[..8] == chd::CHD_MAGIC {
+        return Ok(Box::new(ChdImage::new(file)?));
+    }
+
+    if magic[..4] == ciso::CISO_MAGIC || magic[..4] == ciso::ZISO_MAGIC {
+        return Ok(Box::new(CisoImage::new(file)?));
+    }
+
+    if magic[..12] == raw::CD_SYNC_HEADER {
+        return Ok(Box::new(RawImage::detect(file)?));
+    }
+
+    Ok(Box::new(PlainImage::new(file)?))
+}
+
+/// Hands `f` image at `path`, or start of image inside archive.
+///
+/// Archives are decompressed only as far as the start goes.
+pub fn with_image_start<T, F>(path: &Path, f: F) -> Result<T>
+where
+    F: FnOnce(ImageStart) -> Result<T>,
+{
+    let kind = match archive::detect(path)? {
+        Some(kind) => kind,
+        None => {
+            let image = open(path)?;
+            let (media, size) = (image.media(), image.size());
+            return f(ImageStart {
+                image: Box::new(image),
+                media,
+                size: Some(size),
+                whole: true,
+            });
+        }
+    };
+
+    let mut result = None;
+    archive::with_image(path, kind, |stream| {
+        let start = ImageStart {
+            image: Box::new(stream.prefix()),
+            media: stream.media(),
+            size: stream.size(),
+            whole: false,
+        };
+        result = Some(f(start)?);
+        Ok(())
+    })?;
+
+    result.ok_or_else(|| Error::from(ErrorKind::NotFound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_open_plain_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let image = open(&path).unwrap();
+        assert_eq!(image.size(), 358400);
+        assert_eq!(image.media(), MediaType::Dvd);
+    }
+
+    #[test]
+    fn test_open_ciso_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.cso");
+        let image = open(&path).unwrap();
+        assert_eq!(image.size(), 358400);
+    }
+
+    #[test]
+    fn test_open_ciso_matches_plain() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let mut expected = Vec::new();
+        open(&path).unwrap().read_to_end(&mut expected).unwrap();
+
+        path.set_extension("cso");
+        let mut decoded = Vec::new();
+        open(&path).unwrap().read_to_end(&mut decoded).unwrap();
+        assert!(expected == decoded);
+    }
+
+    #[test]
+    fn test_open_ziso_matches_plain() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let mut expected = Vec::new();
+        open(&path).unwrap().read_to_end(&mut expected).unwrap();
+
+        path.set_extension("zso");
+        let mut decoded = Vec::new();
+        open(&path).unwrap().read_to_end(&mut decoded).unwrap();
+        assert!(expected == decoded);
+    }
+
+    #[test]
+    fn test_open_chd_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.chd");
+        let image = open(&path).unwrap();
+        assert_eq!(image.size(), 358400);
+    }
+
+    #[test]
+    fn test_open_raw_image() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.bin");
+        let image = open(&path).unwrap();
+        assert_eq!(image.size(), 358400);
+        assert_eq!(image.media(), MediaType::Cd);
+    }
+
+    #[test]
+    fn test_open_cue_matches_plain() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let mut expected = Vec::new();
+        open(&path).unwrap().read_to_end(&mut expected).unwrap();
+
+        path.set_extension("cue");
+        let mut decoded = Vec::new();
+        let mut image = open(&path).unwrap();
+        image.read_to_end(&mut decoded).unwrap();
+        assert_eq!(image.media(), MediaType::Cd);
+        assert!(expected == decoded);
+    }
+
+    #[test]
+    fn test_open_file_not_found() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/foo.iso");
+        assert!(open(&path).is_err());
+    }
+}
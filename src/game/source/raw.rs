@@ -0,0 +1,276 @@
+use crate::game::media::MediaType;
+use crate::game::source::ImageSource;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+pub const CD_SYNC_HEADER: [u8; 12] = [
+    0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00,
+];
+const CD_MODE_OFFSET: usize = 15;
+const CD_FORM2_SUBMODE: u8 = 0x20;
+const SECTOR_SIZE: u64 = 2048;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrackMode {
+    Mode1Cooked,
+    Mode1Raw,
+    Mode2Cooked,
+    Mode2Raw,
+}
+
+impl TrackMode {
+    pub fn from_cue(mode: &str) -> Option<Self> {
+        match mode {
+            "MODE1/2048" => Some(TrackMode::Mode1Cooked),
+            "MODE1/2352" => Some(TrackMode::Mode1Raw),
+            "MODE2/2336" => Some(TrackMode::Mode2Cooked),
+            "MODE2/2352" => Some(TrackMode::Mode2Raw),
+            _ => None,
+        }
+    }
+
+    pub fn sector_size(&self) -> u64 {
+        match self {
+            TrackMode::Mode1Cooked => 2048,
+            TrackMode::Mode2Cooked => 2336,
+            TrackMode::Mode1Raw | TrackMode::Mode2Raw => 2352,
+        }
+    }
+
+    fn data_offset(&self) -> usize {
+        match self {
+            TrackMode::Mode1Cooked => 0,
+            TrackMode::Mode2Cooked => 8,
+            TrackMode::Mode1Raw => 16,
+            TrackMode::Mode2Raw => 24,
+        }
+    }
+
+    /// Checks that first sector of track is what the mode says.
+    ///
+    /// Later sectors are not checked, as tracks end with form 2 postgap
+    /// and may stream XA form 2 data, of which first 2048 bytes are taken.
+    fn validate(&self, sector: &[u8]) -> Result<()> {
+        // Raw sectors carry sync pattern and mode byte, XA (mode 2) sectors
+        // also carry subheader that has to describe form 1 (2048 bytes) data.
+        let valid = match self {
+            TrackMode::Mode1Cooked => true,
+            TrackMode::Mode2Cooked => sector[2] & CD_FORM2_SUBMODE == 0,
+            TrackMode::Mode1Raw => sector[..12] == CD_SYNC_HEADER && sector[CD_MODE_OFFSET] == 1,
+            TrackMode::Mode2Raw => {
+                sector[..12] == CD_SYNC_HEADER
+                    && sector[CD_MODE_OFFSET] == 2
+                    && sector[18] & CD_FORM2_SUBMODE == 0
+            }
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(Error::from(ErrorKind::InvalidData)),
+        }
+    }
+}
+
+/// Raw CD dump (e.g. `.bin`) read as 2048-byte user data sectors.
+pub struct RawImage {
+    file: File,
+    mode: TrackMode,
+    first_sector: u64,
+    size: u64,
+    position: u64,
+    cached_sector: Option<u64>,
+    cache: Vec<u8>,
+}
+
+impl RawImage {
+    /// Opens data track of `mode` starting at `first_sector` of `file`.
+    ///
+    /// Track spans `sectors` sectors, or until the end of the file if not given.
+    pub fn new(
+        file: File,
+        mode: TrackMode,
+        first_sector: u64,
+        sectors: Option<u64>,
+    ) -> Result<Self> {
+        let length = file.metadata()?.len();
+        let available = (length / mode.sector_size()).saturating_sub(first_sector);
+        let sectors = sectors.unwrap_or(available);
+
+        if sectors == 0 || sectors > available {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let mut image = RawImage {
+            file,
+            mode,
+            first_sector,
+            size: sectors * SECTOR_SIZE,
+            position: 0,
+            cached_sector: None,
+            cache: vec![0x00; mode.sector_size() as usize],
+        };
+
+        // Fail early if first sector is not what the track mode says.
+        image.load_sector(0)?;
+        mode.validate(&image.cache)?;
+        Ok(image)
+    }
+
+    /// Opens raw dump without cue sheet, guessing mode from its first sector.
+    pub fn detect(mut file: File) -> Result<Self> {
+        let mut header = [0x00; CD_MODE_OFFSET + 1];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        if header[..12] != CD_SYNC_HEADER {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let mode = match header[CD_MODE_OFFSET] {
+            1 => TrackMode::Mode1Raw,
+            2 => TrackMode::Mode2Raw,
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+
+        RawImage::new(file, mode, 0, None)
+    }
+
+    fn load_sector(&mut self, sector: u64) -> Result<()> {
+        if self.cached_sector == Some(sector) {
+            return Ok(());
+        }
+
+        let offset = (self.first_sector + sector) * self.mode.sector_size();
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut self.cache)?;
+
+        self.cached_sector = Some(sector);
+        Ok(())
+    }
+}
+
+impl Read for RawImage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let sector = self.position / SECTOR_SIZE;
+        let offset = (self.position % SECTOR_SIZE) as usize;
+        self.load_sector(sector)?;
+
+        let start = self.mode.data_offset() + offset;
+        let count = (SECTOR_SIZE as usize - offset).min(buf.len());
+        buf[..count].copy_from_slice(&self.cache[start..start + count]);
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for RawImage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.size as i64 + p,
+            SeekFrom::Current(p) => self.position as i64 + p,
+        };
+
+        if position < 0 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+impl ImageSource for RawImage {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn media(&self) -> MediaType {
+        MediaType::Cd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_raw_detect_mode2() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.bin");
+        let image = RawImage::detect(File::open(path).unwrap()).unwrap();
+        assert_eq!(image.mode, TrackMode::Mode2Raw);
+        assert_eq!(image.size(), 358400);
+    }
+
+    #[test]
+    fn test_raw_read_system_cnf() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.bin");
+        let mut image = RawImage::detect(File::open(path).unwrap()).unwrap();
+        let mut buffer = [0x00; 29];
+        image.seek(SeekFrom::Start(49152)).unwrap();
+        image.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"BOOT2 = cdrom0:\\SLXS_123.45;1");
+    }
+
+    #[test]
+    fn test_raw_read_form2_postgap() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.bin");
+        let mut data = std::fs::read(path).unwrap();
+
+        // Redump dumps end data track with form 2 postgap sectors.
+        let mut postgap = vec![0x00; 2352];
+        postgap[..12].copy_from_slice(&CD_SYNC_HEADER);
+        postgap[CD_MODE_OFFSET] = 2;
+        postgap[18] = CD_FORM2_SUBMODE;
+        postgap[24..2072].fill(0xaa);
+        for _ in 0..2 {
+            data.extend_from_slice(&postgap);
+        }
+
+        let dir = TestDir::new("raw_form2");
+        let binpath = dir.join("postgap.bin");
+        std::fs::write(&binpath, data).unwrap();
+
+        let mut image = RawImage::detect(File::open(binpath).unwrap()).unwrap();
+        assert_eq!(image.size(), 358400 + 2 * 2048);
+        let mut contents = Vec::new();
+        image.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len(), 358400 + 2 * 2048);
+        assert!(contents[358400..].iter().all(|b| *b == 0xaa));
+    }
+
+    #[test]
+    fn test_raw_detect_plain_iso() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let image = RawImage::detect(File::open(path).unwrap());
+        assert!(image.is_err());
+    }
+
+    #[test]
+    fn test_raw_wrong_mode() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.bin");
+        let file = File::open(path).unwrap();
+        let image = RawImage::new(file, TrackMode::Mode1Raw, 0, None);
+        assert!(image.is_err());
+    }
+
+    #[test]
+    fn test_track_mode_from_cue() {
+        assert_eq!(TrackMode::from_cue("MODE2/2352"), Some(TrackMode::Mode2Raw));
+        assert_eq!(TrackMode::from_cue("AUDIO"), None);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::game::media::MediaType;
+
+use std::io::prelude::*;
+use std::io::{Cursor, Result};
+
+// SYSTEM.CNF sits close to the beginning of PS2 discs,
+// so first 16MB of image are enough to read it.
+const STREAM_PREFIX_SIZE: usize = 16 * 1024 * 1024;
+
+/// Non-seekable image, e.g. piped through stdin.
+///
+/// First sectors are kept in memory so `SYSTEM.CNF` can be read before
+/// the rest of the stream is written out sequentially.
+pub struct StreamImage<'a> {
+    prefix: Vec<u8>,
+    inner: Box<dyn Read + 'a>,
+    size: Option<u64>,
+    media: MediaType,
+    position: usize,
+}
+
+impl<'a> StreamImage<'a> {
+    /// Wraps `inner` stream, optionally of known `size` in bytes.
+    pub fn new(mut inner: Box<dyn Read + 'a>, size: Option<u64>) -> Result<Self> {
+        let mut prefix = Vec::with_capacity(STREAM_PREFIX_SIZE);
+        (&mut inner)
+            .take(STREAM_PREFIX_SIZE as u64)
+            .read_to_end(&mut prefix)?;
+
+        // Whole image fit into prefix, so its size is known regardless.
+        let size = match prefix.len() < STREAM_PREFIX_SIZE {
+            true => Some(prefix.len() as u64),
+            false => size,
+        };
+
+        Ok(StreamImage {
+            prefix,
+            inner,
+            size,
+            media: MediaType::Dvd,
+            position: 0,
+        })
+    }
+
+    /// Marks stream as dumped from `media`, which cannot be told from data alone.
+    pub fn with_media(mut self, media: MediaType) -> Self {
+        self.media = media;
+        self
+    }
+
+    /// Seekable view of buffered beginning of the image.
+    pub fn prefix(&self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.prefix.clone())
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    pub fn media(&self) -> MediaType {
+        self.media
+    }
+}
+
+impl Read for StreamImage<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.position < self.prefix.len() {
+            let count = (self.prefix.len() - self.position).min(buf.len());
+            buf[..count].copy_from_slice(&self.prefix[self.position..self.position + count]);
+            self.position += count;
+            return Ok(count);
+        }
+
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_stream_short_image_size() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/testimage.iso");
+        let file = File::open(path).unwrap();
+        let stream = StreamImage::new(Box::new(file), None).unwrap();
+        assert_eq!(stream.size(), Some(358400));
+    }
+
+    #[test]
+    fn test_stream_reads_prefix_then_rest() {
+        let data = (0..STREAM_PREFIX_SIZE + 10)
+            .map(|b| b as u8)
+            .collect::<Vec<u8>>();
+        let mut stream = StreamImage::new(Box::new(Cursor::new(data.clone())), None).unwrap();
+        assert_eq!(stream.size(), None);
+
+        let mut buffer = Vec::new();
+        stream.read_to_end(&mut buffer).unwrap();
+        assert!(buffer == data);
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+
+use iso9660::{DirectoryEntry, ISO9660};
+
+const SYSTEM_CNF_PATH: &str = "/SYSTEM.CNF";
+
+/// Video mode game boots in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoMode {
+    Ntsc,
+    Pal,
+}
+
+impl std::fmt::Display for VideoMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VideoMode::Ntsc => write!(f, "NTSC"),
+            VideoMode::Pal => write!(f, "PAL"),
+        }
+    }
+}
+
+impl std::str::FromStr for VideoMode {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode.to_ascii_uppercase().as_str() {
+            "NTSC" => Ok(VideoMode::Ntsc),
+            "PAL" => Ok(VideoMode::Pal),
+            _ => Err(Error::from(ErrorKind::InvalidData)),
+        }
+    }
+}
+
+/// Contents of `SYSTEM.CNF`, telling console how to boot the disc.
+///
+/// ```text
+/// BOOT2 = cdrom0:\SLUS_200.62;1
+/// VER = 1.00
+/// VMODE = NTSC
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct SystemCnf {
+    /// PS2 boot path from `BOOT2`.
+    pub boot2: Option<String>,
+    /// PS1 boot path from `BOOT`, which PS2 discs do not have.
+    pub boot: Option<String>,
+    pub version: Option<String>,
+    /// From `VMODE`, or `VIDEO` used by some discs instead.
+    pub video_mode: Option<VideoMode>,
+    pub hdd_unit_power: Option<String>,
+}
+
+impl SystemCnf {
+    /// Parses `key = value` lines, in any order and with any line endings.
+    ///
+    /// Malformed lines and unknown video modes are skipped, as console does,
+    /// so only missing boot path fails.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut cnf = SystemCnf {
+            boot2: None,
+            boot: None,
+            version: None,
+            video_mode: None,
+            hdd_unit_power: None,
+        };
+
+        // Discs pad the file with zeros or spaces.
+        let lines = contents
+            .split(['\r', '\n'])
+            .map(|l| l.trim_matches(|c: char| c.is_whitespace() || c == '\0'));
+
+        for line in lines.filter(|l| !l.is_empty()) {
+            let (key, value) = match line.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let value = String::from(value.trim());
+
+            match key.trim().to_ascii_uppercase().as_str() {
+                "BOOT2" => cnf.boot2 = Some(value),
+                "BOOT" => cnf.boot = Some(value),
+                "VER" => cnf.version = Some(value),
+                "VMODE" | "VIDEO" => cnf.video_mode = value.parse().ok(),
+                "HDDUNITPOWER" => cnf.hdd_unit_power = Some(value),
+                _ => (),
+            }
+        }
+
+        if cnf.boot2.is_none() && cnf.boot.is_none() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Ok(cnf)
+    }
+
+    /// Reads `SYSTEM.CNF` from root of ISO9660 `image`.
+    pub fn read<T: Read + Seek>(image: T) -> Result<Self> {
+        let iso = ISO9660::new(image).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+        let entry = iso
+            .open(SYSTEM_CNF_PATH)
+            .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+        match entry {
+            Some(DirectoryEntry::File(file)) => {
+                let mut buffer = Vec::new();
+                file.read().read_to_end(&mut buffer)?;
+                SystemCnf::parse(&String::from_utf8_lossy(&buffer))
+            }
+            _ => Err(Error::from(ErrorKind::NotFound)),
+        }
+    }
+
+    /// Path of boot executable on disc, e.g. `SLUS_200.62` or `DIR\GAME.ELF`,
+    /// without device and `;1` version suffix.
+    pub fn boot_file(&self) -> &str {
+        let path = self.boot2.as_ref().or(self.boot.as_ref()).unwrap();
+        let path = path.split_once(':').map(|(_, p)| p).unwrap_or(path);
+        let path = path.rsplit_once(';').map(|(p, _)| p).unwrap_or(path);
+        path.trim_start_matches(['\\', '/'])
+    }
+
+    /// Game serial, which is name of boot executable, e.g. `SLUS_200.62`.
+    pub fn serial(&self) -> Result<String> {
+        let serial = self.boot_file().rsplit(['\\', '/']).next().unwrap();
+        match serial.is_empty() {
+            true => Err(Error::from(ErrorKind::InvalidData)),
+            false => Ok(String::from(serial)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_system_cnf() {
+        let contents = "VMODE = PAL\r\nVER = 1.01\r\nBOOT2 = cdrom0:\\SLES_503.30;1\r\n\0\0";
+        let cnf = SystemCnf::parse(contents).unwrap();
+        assert_eq!(cnf.serial().unwrap(), "SLES_503.30");
+        assert_eq!(cnf.version.as_deref(), Some("1.01"));
+        assert_eq!(cnf.video_mode, Some(VideoMode::Pal));
+
+        let cnf = SystemCnf::parse("boot2=cdrom0:\\DATA\\MAIN.ELF;1\nvideo=ntsc\n").unwrap();
+        assert_eq!(cnf.boot_file(), "DATA\\MAIN.ELF");
+        assert_eq!(cnf.serial().unwrap(), "MAIN.ELF");
+        assert_eq!(cnf.video_mode, Some(VideoMode::Ntsc));
+    }
+
+    #[test]
+    fn test_parse_system_cnf_ps1() {
+        let cnf = SystemCnf::parse("BOOT = cdrom:\\SLUS_005.94;1\nTCB = 4\n").unwrap();
+        assert!(cnf.boot2.is_none());
+        assert_eq!(cnf.serial().unwrap(), "SLUS_005.94");
+    }
+
+    #[test]
+    fn test_parse_system_cnf_junk_lines() {
+        let contents = "BOOT2 = cdrom0:\\SLES_503.30;1\nPARAMETER\nVMODE = SECAM\n";
+        let cnf = SystemCnf::parse(contents).unwrap();
+        assert_eq!(cnf.serial().unwrap(), "SLES_503.30");
+        assert_eq!(cnf.video_mode, None);
+    }
+
+    #[test]
+    fn test_parse_system_cnf_errors() {
+        let error = |c: &str| SystemCnf::parse(c).unwrap_err().kind();
+        assert_eq!(error("VER = 1.00\n"), ErrorKind::InvalidData);
+        assert_eq!(error("garbage\n"), ErrorKind::InvalidData);
+        assert!(SystemCnf::parse("BOOT2 = cdrom0:\\;1\n")
+            .unwrap()
+            .serial()
+            .is_err());
+    }
+}
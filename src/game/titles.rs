@@ -0,0 +1,203 @@
+use crate::game::art::normalize_key;
+
+use std::collections::HashMap;
+use std::fs::read;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+// PCSX2 GameIndex.yaml keys holding title and region of a serial.
+const YAML_NAME: &str = "name";
+const YAML_NAME_EN: &str = "name-en";
+const YAML_REGION: &str = "region";
+const CSV_HEADER: &str = "serial";
+
+/// Canonical title of game, with its region when database knows it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleEntry {
+    pub title: String,
+    pub region: Option<String>,
+}
+
+/// Offline serial to title database.
+pub struct TitleDb {
+    entries: HashMap<String, TitleEntry>,
+}
+
+/// Strips YAML quotes from scalar value.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let quoted = |q: char| value.len() >= 2 && value.starts_with(q) && value.ends_with(q);
+
+    if quoted('"') {
+        return value[1..value.len() - 1].replace("\\\"", "\"");
+    }
+
+    if quoted('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+
+    String::from(value)
+}
+
+/// Splits CSV line into fields, honouring double quoted ones.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields.into_iter().map(|f| String::from(f.trim())).collect()
+}
+
+impl TitleDb {
+    /// Loads `.yaml`/`.yml` file as PCSX2 GameIndex, anything else as CSV.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = String::from_utf8_lossy(&read(path)?).to_string();
+        let is_yaml = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml"));
+
+        match is_yaml {
+            true => Ok(TitleDb::parse_yaml(&contents)),
+            false => TitleDb::parse_csv(&contents),
+        }
+    }
+
+    /// Parses PCSX2 GameIndex.yaml, reading only top level serials and
+    /// their name and region. English name is preferred when present.
+    pub fn parse_yaml(contents: &str) -> Self {
+        let mut entries = HashMap::new();
+        let mut serial: Option<String> = None;
+        let mut fields: HashMap<&str, String> = HashMap::new();
+
+        let mut flush = |serial: &Option<String>, fields: &mut HashMap<&str, String>| {
+            let title = fields
+                .remove(YAML_NAME_EN)
+                .or_else(|| fields.remove(YAML_NAME));
+
+            if let (Some(serial), Some(title)) = (serial, title) {
+                let region = fields.remove(YAML_REGION);
+                entries.insert(normalize_key(serial), TitleEntry { title, region });
+            }
+
+            fields.clear();
+        };
+
+        for line in contents.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(' ') {
+                flush(&serial, &mut fields);
+                serial = line.trim_end().strip_suffix(':').map(unquote);
+                continue;
+            }
+
+            // Only direct children of serial, nested keys are more indented.
+            let indent = line.len() - line.trim_start().len();
+            if indent != 2 {
+                continue;
+            }
+
+            let field = line.trim().split_once(':').and_then(|(key, value)| {
+                let known = [YAML_NAME, YAML_NAME_EN, YAML_REGION];
+                Some((known.into_iter().find(|k| *k == key)?, value))
+            });
+
+            if let Some((key, value)) = field {
+                fields.insert(key, unquote(value));
+            }
+        }
+
+        flush(&serial, &mut fields);
+
+        TitleDb { entries }
+    }
+
+    /// Parses `serial,title[,region]` lines, with optional header line.
+    pub fn parse_csv(contents: &str) -> Result<Self> {
+        let mut entries = HashMap::new();
+
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let fields = split_csv(line);
+            if fields[0].eq_ignore_ascii_case(CSV_HEADER) || fields[0].starts_with('#') {
+                continue;
+            }
+
+            if fields.len() < 2 || fields[1].is_empty() {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+
+            let region = fields.get(2).filter(|r| !r.is_empty()).cloned();
+            let entry = TitleEntry {
+                title: fields[1].clone(),
+                region,
+            };
+            entries.insert(normalize_key(&fields[0]), entry);
+        }
+
+        Ok(TitleDb { entries })
+    }
+
+    /// Looks up `serial` in any notation, e.g. `SLUS_200.62` or `SLUS-20062`.
+    pub fn get(&self, serial: &str) -> Option<&TitleEntry> {
+        self.entries.get(&normalize_key(serial))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml() {
+        let contents = concat!(
+            "# PCSX2 game index\n",
+            "SLUS-20062:\n",
+            "  name: \"Grand Theft Auto III\"\n",
+            "  region: \"NTSC-U\"\n",
+            "  gameFixes:\n",
+            "    - name: \"Nested\"\n",
+            "SLPM-65010:\n",
+            "  name: \"ファイナルファンタジーX\"\n",
+            "  name-en: 'Final Fantasy X'\n",
+            "  region: \"NTSC-J\"\n",
+        );
+
+        let db = TitleDb::parse_yaml(contents);
+        let gta = db.get("SLUS_200.62").unwrap();
+        assert_eq!(gta.title, "Grand Theft Auto III");
+        assert_eq!(gta.region.as_deref(), Some("NTSC-U"));
+        assert_eq!(db.get("SLPM_650.10").unwrap().title, "Final Fantasy X");
+        assert!(db.get("SLES_123.45").is_none());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let contents = concat!(
+            "serial,title,region\n",
+            "SLUS_200.62,Grand Theft Auto III,NTSC-U\n",
+            "SLES-50330,\"Ratchet & Clank, PAL\"\n",
+        );
+
+        let db = TitleDb::parse_csv(contents).unwrap();
+        assert_eq!(db.get("SLUS-20062").unwrap().title, "Grand Theft Auto III");
+        let ratchet = db.get("SLES_503.30").unwrap();
+        assert_eq!(ratchet.title, "Ratchet & Clank, PAL");
+        assert!(ratchet.region.is_none());
+        assert!(TitleDb::parse_csv("SLUS_200.62\n").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ps2time;
+
+use std::io::{Error, ErrorKind, Result};
+
+pub const SECTOR_SIZE: u64 = 512;
+pub const APA_HEADER_SIZE: usize = 1024;
+pub const APA_ID_SIZE: usize = 32;
+pub const APA_MAX_SUBS: usize = 64;
+// Partitions are power of two multiples of 128MB, aligned to their own size.
+pub const APA_MIN_SECTORS: u32 = 0x40000;
+
+pub const APA_TYPE_FREE: u16 = 0x0000;
+pub const APA_TYPE_MBR: u16 = 0x0001;
+pub const APA_TYPE_HDL: u16 = 0x1337;
+
+const APA_MAGIC: u32 = 0x00415041;
+const APA_FLAG_SUB: u16 = 0x0001;
+const APA_MBR_MAGIC: &[u8; 32] = b"Sony Computer Entertainment Inc.";
+const APA_MBR_VERSION: u8 = 2;
+
+const APA_CHECKSUM: usize = 0x000;
+const APA_MAGIC_OFFSET: usize = 0x004;
+const APA_NEXT: usize = 0x008;
+const APA_PREV: usize = 0x00c;
+const APA_ID: usize = 0x010;
+const APA_START: usize = 0x040;
+const APA_LENGTH: usize = 0x044;
+const APA_TYPE: usize = 0x048;
+const APA_FLAGS: usize = 0x04a;
+const APA_NSUB: usize = 0x04c;
+const APA_CREATED: usize = 0x050;
+const APA_MAIN: usize = 0x058;
+const APA_NUMBER: usize = 0x05c;
+const APA_MBR_MAGIC_OFFSET: usize = 0x100;
+const APA_MBR_VERSION_OFFSET: usize = 0x120;
+const APA_MBR_CREATED: usize = 0x128;
+const APA_SUBS: usize = 0x200;
+
+/// Header in the first sectors of every APA partition.
+///
+/// Raw bytes are kept as read, so fields ulmake does not know about
+/// survive rewriting neighbouring headers when partitions are linked in.
+pub struct ApaHeader {
+    raw: Vec<u8>,
+}
+
+impl ApaHeader {
+    pub fn new(id: &str, start: u32, length: u32, kind: u16) -> Self {
+        let mut header = ApaHeader {
+            raw: vec![0x00; APA_HEADER_SIZE],
+        };
+
+        let id = &id.as_bytes()[..id.len().min(APA_ID_SIZE)];
+        header.set_u32(APA_MAGIC_OFFSET, APA_MAGIC);
+        header.raw[APA_ID..APA_ID + id.len()].copy_from_slice(id);
+        header.set_u32(APA_START, start);
+        header.set_u32(APA_LENGTH, length);
+        header.set_u16(APA_TYPE, kind);
+        header.raw[APA_CREATED..APA_CREATED + 8].copy_from_slice(&ps2time::now());
+
+        if kind == APA_TYPE_MBR {
+            header.raw[APA_MBR_MAGIC_OFFSET..APA_MBR_MAGIC_OFFSET + 32]
+                .copy_from_slice(APA_MBR_MAGIC);
+            header.raw[APA_MBR_VERSION_OFFSET] = APA_MBR_VERSION;
+            header.raw[APA_MBR_CREATED..APA_MBR_CREATED + 8].copy_from_slice(&ps2time::now());
+        }
+
+        header
+    }
+
+    /// Sub-partition `number` (counted from 1) of `main` partition.
+    pub fn new_sub(main: &ApaHeader, number: u32, start: u32, length: u32) -> Self {
+        let mut header = ApaHeader::new(&main.id(), start, length, main.kind());
+        header.set_u16(APA_FLAGS, APA_FLAG_SUB);
+        header.set_u32(APA_MAIN, main.start());
+        header.set_u32(APA_NUMBER, number);
+        header
+    }
+
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < APA_HEADER_SIZE {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let header = ApaHeader {
+            raw: buffer[..APA_HEADER_SIZE].to_vec(),
+        };
+
+        if header.get_u32(APA_MAGIC_OFFSET) != APA_MAGIC
+            || header.get_u32(APA_CHECKSUM) != header.checksum()
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Ok(header)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = self.raw.clone();
+        raw[APA_CHECKSUM..APA_CHECKSUM + 4].copy_from_slice(&self.checksum().to_le_bytes());
+        raw
+    }
+
+    fn checksum(&self) -> u32 {
+        // Sum of all words following the checksum itself.
+        self.raw[4..]
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .fold(0, |acc, w| acc.wrapping_add(w))
+    }
+
+    fn get_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.raw[offset], self.raw[offset + 1]])
+    }
+
+    fn get_u32(&self, offset: usize) -> u32 {
+        let mut bytes = [0x00; 4];
+        bytes.copy_from_slice(&self.raw[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn set_u16(&mut self, offset: usize, value: u16) {
+        self.raw[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_u32(&mut self, offset: usize, value: u32) {
+        self.raw[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn id(&self) -> String {
+        let id = &self.raw[APA_ID..APA_ID + APA_ID_SIZE];
+        let end = id.iter().position(|b| *b == 0x00).unwrap_or(APA_ID_SIZE);
+        String::from_utf8_lossy(&id[..end]).to_string()
+    }
+
+    pub fn next(&self) -> u32 {
+        self.get_u32(APA_NEXT)
+    }
+
+    pub fn set_next(&mut self, next: u32) {
+        self.set_u32(APA_NEXT, next);
+    }
+
+    pub fn set_prev(&mut self, prev: u32) {
+        self.set_u32(APA_PREV, prev);
+    }
+
+    pub fn start(&self) -> u32 {
+        self.get_u32(APA_START)
+    }
+
+    pub fn length(&self) -> u32 {
+        self.get_u32(APA_LENGTH)
+    }
+
+    pub fn kind(&self) -> u16 {
+        self.get_u16(APA_TYPE)
+    }
+
+    pub fn is_sub(&self) -> bool {
+        self.get_u16(APA_FLAGS) & APA_FLAG_SUB != 0
+    }
+
+    pub fn set_subs(&mut self, subs: &[(u32, u32)]) {
+        self.set_u32(APA_NSUB, subs.len() as u32);
+        for (i, (start, length)) in subs.iter().enumerate() {
+            self.set_u32(APA_SUBS + i * 8, *start);
+            self.set_u32(APA_SUBS + i * 8 + 4, *length);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let mut header = ApaHeader::new("PP.FOO", 0x40000, 0x40000, APA_TYPE_HDL);
+        header.set_next(0x80000);
+        header.set_subs(&[(0x80000, 0x40000)]);
+
+        let parsed = ApaHeader::parse(&header.to_bytes()).unwrap();
+        assert_eq!(parsed.id(), "PP.FOO");
+        assert_eq!(parsed.start(), 0x40000);
+        assert_eq!(parsed.next(), 0x80000);
+        assert_eq!(parsed.kind(), APA_TYPE_HDL);
+    }
+
+    #[test]
+    fn test_header_bad_checksum() {
+        let mut bytes = ApaHeader::new("__mbr", 0, APA_MIN_SECTORS, APA_TYPE_MBR).to_bytes();
+        bytes[APA_ID] = 0x41;
+        assert!(ApaHeader::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_mbr_magic() {
+        let bytes = ApaHeader::new("__mbr", 0, APA_MIN_SECTORS, APA_TYPE_MBR).to_bytes();
+        assert_eq!(&bytes[0x100..0x120], APA_MBR_MAGIC);
+        assert_eq!(bytes[0x120], APA_MBR_VERSION);
+    }
+
+    #[test]
+    fn test_sub_header() {
+        let main = ApaHeader::new("PP.FOO", 0x40000, 0x40000, APA_TYPE_HDL);
+        let sub = ApaHeader::new_sub(&main, 1, 0x80000, 0x40000);
+        assert!(sub.is_sub());
+        assert_eq!(sub.id(), "PP.FOO");
+        assert_eq!(sub.kind(), APA_TYPE_HDL);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::game::media::MediaType;
+
+use std::io::{Error, ErrorKind, Result};
+
+// HD Loader header sits 1MB + 4KB into the main partition,
+// and game data starts after first 4MB of each partition slice.
+pub const HDL_HEADER_OFFSET: u64 = 0x101000;
+pub const HDL_HEADER_SIZE: usize = 1024;
+pub const HDL_DATA_OFFSET: u32 = 0x2000;
+pub const HDL_MAX_PARTS: usize = 65;
+
+const HDL_MAGIC: u32 = 0xdeadfeed;
+const HDL_NAME: usize = 0x008;
+const HDL_NAME_SIZE: usize = 160;
+const HDL_STARTUP: usize = 0x0ac;
+const HDL_STARTUP_SIZE: usize = 60;
+const HDL_LAYER_BREAK: usize = 0x0e8;
+const HDL_MEDIA: usize = 0x0ec;
+const HDL_NUM_PARTS: usize = 0x0f0;
+const HDL_PARTS: usize = 0x0f4;
+
+/// Slice of game data on disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HdlPart {
+    /// Offset of the slice within the image, in 2048-byte sectors.
+    pub offset: u32,
+    /// First disk sector of the slice.
+    pub start: u32,
+    /// Length of the slice in 512-byte disk sectors.
+    pub length: u32,
+}
+
+/// HD Loader game header, as written by hdl_dump and read by OPL.
+#[derive(Debug, PartialEq)]
+pub struct HdlHeader {
+    pub name: String,
+    pub startup: String,
+    pub media: MediaType,
+    pub layer_break: u32,
+    pub parts: Vec<HdlPart>,
+}
+
+fn get_u32(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0x00; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn get_str(buffer: &[u8], offset: usize, size: usize) -> String {
+    let field = &buffer[offset..offset + size];
+    let end = field.iter().position(|b| *b == 0x00).unwrap_or(size);
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+impl HdlHeader {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < HDL_HEADER_SIZE || get_u32(buffer, 0) != HDL_MAGIC {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let num_parts = get_u32(buffer, HDL_NUM_PARTS) as usize;
+        if num_parts == 0 || num_parts > HDL_MAX_PARTS {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let parts = (0..num_parts)
+            .map(|i| HDL_PARTS + i * 12)
+            .map(|o| HdlPart {
+                offset: get_u32(buffer, o),
+                start: get_u32(buffer, o + 4),
+                length: get_u32(buffer, o + 8),
+            })
+            .collect();
+
+        Ok(HdlHeader {
+            name: get_str(buffer, HDL_NAME, HDL_NAME_SIZE),
+            startup: get_str(buffer, HDL_STARTUP, HDL_STARTUP_SIZE),
+            media: MediaType::from(buffer[HDL_MEDIA]),
+            layer_break: get_u32(buffer, HDL_LAYER_BREAK),
+            parts,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![0x00; HDL_HEADER_SIZE];
+        let name = &self.name.as_bytes()[..self.name.len().min(HDL_NAME_SIZE - 1)];
+        let startup = &self.startup.as_bytes()[..self.startup.len().min(HDL_STARTUP_SIZE - 1)];
+
+        buffer[0..4].copy_from_slice(&HDL_MAGIC.to_le_bytes());
+        buffer[HDL_NAME..HDL_NAME + name.len()].copy_from_slice(name);
+        buffer[HDL_STARTUP..HDL_STARTUP + startup.len()].copy_from_slice(startup);
+        buffer[HDL_LAYER_BREAK..HDL_LAYER_BREAK + 4]
+            .copy_from_slice(&self.layer_break.to_le_bytes());
+        buffer[HDL_MEDIA] = u8::from(self.media);
+        buffer[HDL_NUM_PARTS..HDL_NUM_PARTS + 4]
+            .copy_from_slice(&(self.parts.len() as u32).to_le_bytes());
+
+        for (i, part) in self.parts.iter().take(HDL_MAX_PARTS).enumerate() {
+            let o = HDL_PARTS + i * 12;
+            buffer[o..o + 4].copy_from_slice(&part.offset.to_le_bytes());
+            buffer[o + 4..o + 8].copy_from_slice(&part.start.to_le_bytes());
+            buffer[o + 8..o + 12].copy_from_slice(&part.length.to_le_bytes());
+        }
+
+        buffer
+    }
+    /// Size of game data in bytes.
+    pub fn size(&self) -> u64 {
+        self.parts.iter().map(|p| p.length as u64 * 512).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdl_header_roundtrip() {
+        let header = HdlHeader {
+            name: String::from("Foo"),
+            startup: String::from("SLUS_123.45"),
+            media: MediaType::Dvd,
+            layer_break: 0,
+            parts: vec![HdlPart {
+                offset: 0,
+                start: 0x42000,
+                length: 704,
+            }],
+        };
+
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[0..4], &[0xed, 0xfe, 0xad, 0xde]);
+        assert_eq!(bytes[HDL_MEDIA], 0x14);
+        assert_eq!(&bytes[HDL_STARTUP..HDL_STARTUP + 11], b"SLUS_123.45");
+        assert_eq!(bytes[HDL_NUM_PARTS], 1);
+        assert_eq!(
+            &bytes[HDL_PARTS + 4..HDL_PARTS + 8],
+            &[0x00, 0x20, 0x04, 0x00]
+        );
+    }
+}
@@ -0,0 +1,495 @@
+mod apa;
+pub mod hdl;
+
+use crate::game::iso;
+use crate::game::source;
+use crate::hdd::apa::{ApaHeader, APA_HEADER_SIZE, APA_ID_SIZE, APA_MAX_SUBS, APA_MIN_SECTORS};
+use crate::hdd::apa::{APA_TYPE_FREE, APA_TYPE_HDL, APA_TYPE_MBR, SECTOR_SIZE};
+use crate::hdd::hdl::HDL_MAX_PARTS;
+use crate::hdd::hdl::{HdlHeader, HdlPart, HDL_DATA_OFFSET, HDL_HEADER_OFFSET, HDL_HEADER_SIZE};
+use crate::ul::table;
+
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{copy, stdout, Error, ErrorKind, Result, SeekFrom};
+use std::path::Path;
+
+// Largest partition APA allows, 32GB.
+const APA_MAX_SECTORS: u32 = 0x4000000;
+const ISO_SECTOR_SIZE: u64 = 2048;
+
+/// Starts of newly placed partitions, along with (start, length) of free
+/// partitions that fill the gaps needed to keep them aligned.
+struct Allocation {
+    starts: Vec<u32>,
+    gaps: Vec<(u32, u32)>,
+}
+
+/// PS2 internal hard drive image with APA partition table.
+pub struct HddImage {
+    file: File,
+    sectors: u32,
+    partitions: Vec<ApaHeader>,
+}
+
+impl HddImage {
+    /// Creates empty APA formatted image of `size` bytes at `path`.
+    pub fn create(path: &Path, size: u64) -> Result<Self> {
+        let sectors = u32::try_from(size / SECTOR_SIZE).map_err(|_| ErrorKind::InvalidInput)?;
+        if sectors < 2 * APA_MIN_SECTORS {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.set_len(size)?;
+
+        let mut hdd = HddImage {
+            file,
+            sectors,
+            partitions: Vec::new(),
+        };
+
+        let mbr = ApaHeader::new("__mbr", 0, APA_MIN_SECTORS, APA_TYPE_MBR);
+        hdd.write_header(&mbr)?;
+        hdd.partitions.push(mbr);
+        Ok(hdd)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        HddImage::open_with(path, true)
+    }
+
+    /// Opens image only for reading, e.g. to list or extract games
+    /// from image on read-only media.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        HddImage::open_with(path, false)
+    }
+
+    fn open_with(path: &Path, writable: bool) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(writable).open(path)?;
+        let size = file.metadata()?.len();
+        let sectors = u32::try_from(size / SECTOR_SIZE).map_err(|_| ErrorKind::InvalidData)?;
+
+        let mut hdd = HddImage {
+            file,
+            sectors,
+            partitions: Vec::new(),
+        };
+
+        // Partitions form a ring starting and ending at MBR.
+        let mut sector = 0;
+        loop {
+            let header = hdd.read_header(sector)?;
+            sector = header.next();
+            hdd.partitions.push(header);
+
+            if sector == 0 {
+                break;
+            }
+
+            if hdd.partitions.len() > hdd.sectors as usize / APA_MIN_SECTORS as usize {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        }
+
+        Ok(hdd)
+    }
+
+    fn read_header(&mut self, sector: u32) -> Result<ApaHeader> {
+        let mut buffer = vec![0x00; APA_HEADER_SIZE];
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut buffer)?;
+        ApaHeader::parse(&buffer)
+    }
+
+    fn write_header(&mut self, header: &ApaHeader) -> Result<()> {
+        self.file
+            .seek(SeekFrom::Start(header.start() as u64 * SECTOR_SIZE))?;
+        self.file.write_all(&header.to_bytes())
+    }
+
+    /// Appends `header` to the end of the partition ring.
+    fn link_partition(&mut self, mut header: ApaHeader) -> Result<()> {
+        let last = self.partitions.len() - 1;
+        header.set_prev(self.partitions[last].start());
+        header.set_next(0);
+        self.partitions[last].set_next(header.start());
+        self.partitions[0].set_prev(header.start());
+
+        self.write_header(&header)?;
+        for index in [last, 0] {
+            let bytes = self.partitions[index].to_bytes();
+            let offset = self.partitions[index].start() as u64 * SECTOR_SIZE;
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(&bytes)?;
+        }
+
+        self.partitions.push(header);
+        Ok(())
+    }
+
+    /// Largest partition size this disk allows.
+    fn max_partition_sectors(&self) -> u32 {
+        let mut max = APA_MIN_SECTORS;
+        while max < APA_MAX_SECTORS && max * 2 <= self.sectors / 32 {
+            max *= 2;
+        }
+
+        max
+    }
+
+    /// Places partitions of `sizes` after the last one.
+    fn allocate(&self, sizes: &[u32]) -> Result<Allocation> {
+        let mut end = self
+            .partitions
+            .iter()
+            .map(|p| p.start() as u64 + p.length() as u64)
+            .max()
+            .unwrap_or(0);
+        let mut starts = Vec::new();
+        let mut gaps = Vec::new();
+
+        for size in sizes {
+            let size = *size as u64;
+            let aligned = end.div_ceil(size) * size;
+
+            while end < aligned {
+                let mut gap = APA_MIN_SECTORS as u64;
+                while end % (gap * 2) == 0 && end + gap * 2 <= aligned {
+                    gap *= 2;
+                }
+                gaps.push((end as u32, gap as u32));
+                end += gap;
+            }
+
+            starts.push(end as u32);
+            end += size;
+        }
+
+        if end > self.sectors as u64 {
+            return Err(Error::from(ErrorKind::OutOfMemory));
+        }
+
+        Ok(Allocation { starts, gaps })
+    }
+
+    /// Splits `sectors` of game data into APA sized partition slices.
+    fn slice_sizes(&self, sectors: u64) -> Result<Vec<u32>> {
+        let max = self.max_partition_sectors();
+        let mut remaining = sectors;
+        let mut sizes = Vec::new();
+
+        while remaining > 0 {
+            let mut size = APA_MIN_SECTORS;
+            while size < max && ((size - HDL_DATA_OFFSET) as u64) < remaining {
+                size *= 2;
+            }
+
+            remaining = remaining.saturating_sub((size - HDL_DATA_OFFSET) as u64);
+            sizes.push(size);
+        }
+
+        if sizes.is_empty() || sizes.len() > (APA_MAX_SUBS + 1).min(HDL_MAX_PARTS) {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        Ok(sizes)
+    }
+
+    fn read_hdl_header(&mut self, start: u32) -> Result<HdlHeader> {
+        let mut buffer = vec![0x00; HDL_HEADER_SIZE];
+        let offset = start as u64 * SECTOR_SIZE + HDL_HEADER_OFFSET;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buffer)?;
+        HdlHeader::parse(&buffer)
+    }
+
+    /// HD Loader games in partition order.
+    pub fn games(&mut self) -> Result<Vec<HdlHeader>> {
+        let starts = self
+            .partitions
+            .iter()
+            .filter(|p| p.kind() == APA_TYPE_HDL && !p.is_sub())
+            .map(|p| p.start())
+            .collect::<Vec<u32>>();
+
+        starts.iter().map(|s| self.read_hdl_header(*s)).collect()
+    }
+
+    pub fn list_games(&mut self) -> Result<()> {
+        let games = self.games()?;
+        let name_size = games.iter().map(|g| g.name.len()).fold(32, usize::max);
+        let serial_size = games.iter().map(|g| g.startup.len()).fold(12, usize::max);
+        let col_names = ["Index", "Name", "Serial", "Size", "Media"];
+        let col_sizes = vec![5, name_size, serial_size, 6, 5];
+        let hline = table::make_hline(&col_sizes);
+        let header = table::make_row(
+            col_names.iter().map(|c| c.to_string()).collect(),
+            &col_sizes,
+        );
+
+        println!("{}", hline);
+        println!("{}", header);
+        println!("{}", hline);
+
+        for (pos, game) in games.iter().enumerate() {
+            let contents = vec![
+                pos.to_string(),
+                String::from(&game.name),
+                String::from(&game.startup),
+                format!("{:.2}GB", game.size() as f64 / 1_000_000_000.0),
+                game.media.to_string(),
+            ];
+            println!("{}", table::make_row(contents, &col_sizes));
+        }
+
+        println!("{}", hline);
+        Ok(())
+    }
+
+    /// Reads data of `game` back as plain ISO image.
+    pub fn game_reader(&mut self, game: &HdlHeader) -> GameReader<'_> {
+        GameReader {
+            file: &mut self.file,
+            parts: game.parts.clone(),
+            part: 0,
+            position: 0,
+        }
+    }
+
+    /// Installs image at `isopath` as HD Loader game called `name`.
+    pub fn install_game(&mut self, isopath: &Path, name: &str) -> Result<()> {
+        let mut image = source::open(isopath)?;
+        let serial = iso::read_serial(&mut image)?;
+        // OPL takes layer break of DVD9 games from HDL header.
+        let size = image.size();
+        let layer_break = iso::read_layers(&mut image, size)
+            .ok()
+            .flatten()
+            .and_then(|l| l.layer_break)
+            .unwrap_or(0);
+        image.seek(SeekFrom::Start(0))?;
+
+        let id = partition_id(&serial, name);
+        if self.partitions.iter().any(|p| p.id() == id) {
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+
+        // Game data is stored in whole 2048-byte sectors.
+        let data_sectors = image.size().div_ceil(ISO_SECTOR_SIZE) * 4;
+        let sizes = self.slice_sizes(data_sectors)?;
+        let Allocation { starts, gaps } = self.allocate(&sizes)?;
+
+        let mut parts = Vec::new();
+        let mut offset = 0;
+        for (num, (start, size)) in starts.iter().zip(sizes.iter()).enumerate() {
+            print!("Copying slice {} of {}...", num + 1, sizes.len());
+            stdout().flush().unwrap();
+
+            let capacity = (size - HDL_DATA_OFFSET) as u64;
+            let length = capacity.min(data_sectors - offset);
+            let data_start = start + HDL_DATA_OFFSET;
+
+            self.file
+                .seek(SeekFrom::Start(data_start as u64 * SECTOR_SIZE))?;
+            let written = copy(&mut (&mut image).take(length * SECTOR_SIZE), &mut self.file)?;
+            // Last sector of the image may need padding.
+            let padding = length * SECTOR_SIZE - written;
+            self.file.write_all(&vec![0x00; padding as usize])?;
+
+            parts.push(HdlPart {
+                offset: (offset / 4) as u32,
+                start: data_start,
+                length: length as u32,
+            });
+            offset += length;
+            println!("Done.");
+        }
+
+        let hdl = HdlHeader {
+            name: String::from(name),
+            startup: serial,
+            media: image.media(),
+            layer_break: layer_break as u32,
+            parts,
+        };
+        self.file.seek(SeekFrom::Start(
+            starts[0] as u64 * SECTOR_SIZE + HDL_HEADER_OFFSET,
+        ))?;
+        self.file.write_all(&hdl.to_bytes())?;
+
+        // Partitions are linked in only after data is written, so
+        // a failed copy does not leave half installed game behind.
+        for (start, length) in gaps {
+            self.link_partition(ApaHeader::new("", start, length, APA_TYPE_FREE))?;
+        }
+
+        let mut main = ApaHeader::new(&id, starts[0], sizes[0], APA_TYPE_HDL);
+        let subs = starts[1..]
+            .iter()
+            .copied()
+            .zip(sizes[1..].iter().copied())
+            .collect::<Vec<_>>();
+        main.set_subs(&subs);
+
+        let subs = subs
+            .iter()
+            .enumerate()
+            .map(|(i, (start, length))| ApaHeader::new_sub(&main, i as u32 + 1, *start, *length))
+            .collect::<Vec<_>>();
+
+        self.link_partition(main)?;
+        for sub in subs {
+            self.link_partition(sub)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sequential reader of game data spread over HD Loader partition slices.
+pub struct GameReader<'a> {
+    file: &'a mut File,
+    parts: Vec<HdlPart>,
+    part: usize,
+    position: u64,
+}
+
+impl Read for GameReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while let Some(part) = self.parts.get(self.part) {
+            let remaining = part.length as u64 * SECTOR_SIZE - self.position;
+            if remaining == 0 {
+                self.part += 1;
+                self.position = 0;
+                continue;
+            }
+
+            let offset = part.start as u64 * SECTOR_SIZE + self.position;
+            let count = (buf.len() as u64).min(remaining) as usize;
+            self.file.seek(SeekFrom::Start(offset))?;
+            let read = self.file.read(&mut buf[..count])?;
+            if read == 0 {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+
+            self.position += read as u64;
+            return Ok(read);
+        }
+
+        Ok(0)
+    }
+}
+
+/// Partition name in hdl_dump convention, e.g. `PP.SLUS-12345..NAME`.
+fn partition_id(serial: &str, name: &str) -> String {
+    let mut id = format!("PP.{}..{}", serial.replace('_', "-").replace('.', ""), name);
+    while id.len() > APA_ID_SIZE {
+        id.pop();
+    }
+
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use std::path::PathBuf;
+
+    const TEST_DISK_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+    #[test]
+    fn test_partition_id() {
+        assert_eq!(partition_id("SLUS_123.45", "Foo"), "PP.SLUS-12345..Foo");
+        let id = partition_id("SLUS_123.45", "Foooooooooooooooooooooooooooo");
+        assert_eq!(id.len(), APA_ID_SIZE);
+    }
+
+    #[test]
+    fn test_create_too_small() {
+        let dir = TestDir::new("hdd_small");
+        let path = dir.join("hdd.img");
+        assert!(HddImage::create(&path, 1024 * 1024).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_install_game() {
+        let dir = TestDir::new("hdd_install");
+        let path = dir.join("hdd.img");
+        let mut isopath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        isopath.push("resources/testimage.iso");
+
+        let mut hdd = HddImage::create(&path, TEST_DISK_SIZE).unwrap();
+        hdd.install_game(&isopath, "Foo").unwrap();
+        assert!(hdd.install_game(&isopath, "Foo").is_err());
+
+        let mut hdd = HddImage::open_read_only(&path).unwrap();
+        assert_eq!(hdd.partitions.len(), 2);
+        let game = &hdd.partitions[1];
+        assert_eq!(game.id(), "PP.SLXS-12345..Foo");
+        assert_eq!(game.kind(), APA_TYPE_HDL);
+        assert_eq!(game.start(), APA_MIN_SECTORS);
+
+        let games = hdd.games().unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].name, "Foo");
+        assert_eq!(games[0].startup, "SLXS_123.45");
+        assert_eq!(games[0].size(), 358400);
+
+        let mut data = Vec::new();
+        hdd.game_reader(&games[0]).read_to_end(&mut data).unwrap();
+        assert!(data == std::fs::read(&isopath).unwrap());
+
+        // Game data starts 4MB into the partition.
+        let mut buffer = vec![0x00; 29];
+        let offset = (APA_MIN_SECTORS + HDL_DATA_OFFSET) as u64 * SECTOR_SIZE + 49152;
+        hdd.file.seek(SeekFrom::Start(offset)).unwrap();
+        hdd.file.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"BOOT2 = cdrom0:\\SLXS_123.45;1");
+    }
+
+    #[test]
+    fn test_install_dual_layer_game() {
+        let dir = TestDir::new("hdd_dual_layer");
+        let path = dir.join("hdd.img");
+        let isopath = dir.join("dual.iso");
+
+        // Second layer volume descriptor right where first volume ends.
+        let mut testimage = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        testimage.push("resources/testimage.iso");
+        let mut image = std::fs::read(&testimage).unwrap();
+        let pvd = image[16 * 2048..17 * 2048].to_vec();
+        image.extend_from_slice(&pvd);
+        std::fs::write(&isopath, &image).unwrap();
+
+        let mut hdd = HddImage::create(&path, TEST_DISK_SIZE).unwrap();
+        hdd.install_game(&isopath, "Dual").unwrap();
+        let games = hdd.games().unwrap();
+        assert_eq!(games[0].layer_break, 358400 / 2048 - 16);
+    }
+
+    #[test]
+    fn test_allocate_aligns_with_free_partitions() {
+        let dir = TestDir::new("hdd_allocate");
+        let path = dir.join("hdd.img");
+        let hdd = HddImage::create(&path, TEST_DISK_SIZE).unwrap();
+
+        let allocation = hdd.allocate(&[4 * APA_MIN_SECTORS]).unwrap();
+        assert_eq!(allocation.starts, vec![4 * APA_MIN_SECTORS]);
+        assert_eq!(
+            allocation.gaps,
+            vec![
+                (APA_MIN_SECTORS, APA_MIN_SECTORS),
+                (2 * APA_MIN_SECTORS, 2 * APA_MIN_SECTORS)
+            ]
+        );
+        assert!(hdd.allocate(&[64 * APA_MIN_SECTORS]).is_err());
+    }
+}
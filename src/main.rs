@@ -1,6 +1,13 @@
+mod apps;
 mod commands;
 mod game;
+mod hdd;
+mod ps2time;
+mod settings;
+#[cfg(test)]
+mod testutil;
 mod ul;
+mod vmc;
 
 use clap::{App, Arg};
 
@@ -23,7 +30,12 @@ fn main() {
                         .index(1)
                         .takes_value(true)
                         .required(true)
-                        .help("Path to PlayStation 2 .iso disk image"),
+                        .help(concat!(
+                            "Path to PlayStation 2 .iso, .cso, .zso or .chd disk image\n",
+                            "CD games can also be added from .cue or raw .bin dump\n",
+                            "Images packed in .zip, .7z, .gz or .xz are read without extracting\n",
+                            "Use - to read plain .iso from stdin (requires -n)"
+                        )),
                     Arg::with_name("ulpath")
                         .index(2)
                         .takes_value(true)
@@ -37,9 +49,261 @@ fn main() {
                         .takes_value(true)
                         .help(concat!(
                             "Name under which game will be visible in OPL\n",
-                            "Must be <= 32 characters\n",
+                            "Accented and full-width characters are spelled with ASCII,\n",
+                            "others OPL cannot show are dropped and name is cut to 32 bytes\n",
                             "If not specified, .iso name is taken"
                         )),
+                    Arg::with_name("strict").long("strict").help(concat!(
+                        "Uses name exactly as given, failing if it is longer\n",
+                        "than 32 bytes instead of fixing it"
+                    )),
+                    Arg::with_name("force").long("force").help(concat!(
+                        "Adds image even if it looks truncated or corrupt, e.g. when\n",
+                        "it is shorter than its volume descriptor says or boot ELF is missing"
+                    )),
+                    Arg::with_name("size")
+                        .long("size")
+                        .takes_value(true)
+                        .help(concat!(
+                            "Size in bytes of image read from stdin\n",
+                            "If not specified, chunk count is settled at end of stream"
+                        )),
+                    Arg::with_name("name-from")
+                        .long("name-from")
+                        .takes_value(true)
+                        .possible_values(&["file", "db"])
+                        .help(concat!(
+                            "Where name is taken from when -n is not given\n",
+                            "file uses .iso name, db looks up game serial in --titles"
+                        )),
+                    Arg::with_name("name-template")
+                        .long("name-template")
+                        .takes_value(true)
+                        .help(concat!(
+                            "Template of name, e.g. \"{title} [{region}]\", filled with\n",
+                            "{stem} (.iso name), {serial}, {region}, {label} (volume label)\n",
+                            "and {title} (from --titles, .iso name otherwise)\n",
+                            "Names too long for OPL lose (tags) first, then words\n",
+                            "Default can be set as name_template in ulmake.cfg"
+                        )),
+                    Arg::with_name("titles")
+                        .long("titles")
+                        .takes_value(true)
+                        .help(concat!(
+                            "Offline title database, either PCSX2 GameIndex.yaml\n",
+                            "or CSV with serial,title[,region] lines"
+                        )),
+                ]),
+        )
+        .subcommand(
+            App::new("apps")
+                .about("Manages homebrew ELFs shown in OPL Apps tab")
+                .subcommand(
+                    App::new("add")
+                        .about("Copies PlayStation 2 ELF into APPS/ folder and registers it")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Directory containing ul.cfg file\n",
+                                    "APPS/ folder and conf_apps.cfg are created there if needed"
+                                )),
+                            Arg::with_name("elf")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path to MIPS R5900 .elf file"),
+                            Arg::with_name("name")
+                                .short("n")
+                                .takes_value(true)
+                                .help(concat!(
+                                    "Title under which app will be visible in OPL\n",
+                                    "If not specified, .elf name is taken"
+                                )),
+                            Arg::with_name("folder").long("folder").help(concat!(
+                                "Puts app into APPS/<title>/ folder with title.cfg\n",
+                                "instead of conf_apps.cfg entry (requires OPL 1.1 or newer)"
+                            )),
+                        ]),
+                )
+                .subcommand(
+                    App::new("list")
+                        .about("Lists apps from conf_apps.cfg and APPS/ folders")
+                        .arg(
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing ul.cfg file"),
+                        ),
+                )
+                .subcommand(
+                    App::new("remove")
+                        .about("Unregisters app and deletes its ELF from APPS/ folder")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing ul.cfg file"),
+                            Arg::with_name("app")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Index or title of app, as shown by `ulmake apps list`"),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new("art")
+                .about("Manages cover art and other images OPL shows for games")
+                .subcommand(
+                    App::new("import")
+                        .about(concat!(
+                            "Converts local images into ART/ folder, matching them to games\n",
+                            "by serial or name, e.g. SLUS_123.45_COV.png or Name_BG.jpg"
+                        ))
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Directory containing ul.cfg file\n",
+                                    "ART/ folder is created there if needed"
+                                )),
+                            Arg::with_name("dir")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Directory with .jpg or .png images\n",
+                                    "Images without _COV, _ICO, _BG etc. suffix are used as covers"
+                                )),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new("cheats")
+                .about("Manages cheat codes OPL reads from CHT/<serial>.cht")
+                .subcommand(
+                    App::new("add")
+                        .about(concat!(
+                            "Looks up game in local cheat database and installs its codes\n",
+                            "Also turns on OPL cheat engine for the game"
+                        ))
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Directory containing ul.cfg file\n",
+                                    "CHT/ folder is created there if needed"
+                                )),
+                            Arg::with_name("game")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("ul.cfg index, OPL name or serial of game"),
+                            Arg::with_name("db")
+                                .long("db")
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Cheat database in PS2 Cheat Code or CodeBreaker text format\n",
+                                    "Games are matched by /ID serial, or by title otherwise"
+                                )),
+                        ]),
+                )
+                .subcommand(
+                    App::new("list")
+                        .about("Lists installed cheats, marking enabled ones with [x]")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing ul.cfg file"),
+                            Arg::with_name("game")
+                                .index(2)
+                                .takes_value(true)
+                                .help(concat!(
+                                    "ul.cfg index, OPL name or serial of game\n",
+                                    "If not specified, cheats of all games are listed"
+                                )),
+                        ]),
+                )
+                .subcommand(
+                    App::new("toggle")
+                        .about("Enables disabled cheats and disables enabled ones")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing ul.cfg file"),
+                            Arg::with_name("game")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("ul.cfg index, OPL name or serial of game"),
+                            Arg::with_name("cheat")
+                                .index(3)
+                                .takes_value(true)
+                                .required(true)
+                                .multiple(true)
+                                .help("Number or name of cheat, as shown by `ulmake cheats list`"),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new("config")
+                .about(concat!(
+                    "Shows and edits OPL per-game settings kept in CFG/<serial>.cfg\n",
+                    "Without options, current settings are only printed"
+                ))
+                .args(&[
+                    Arg::with_name("ulpath")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help(concat!(
+                            "Directory containing ul.cfg file\n",
+                            "CFG/ folder is created there if needed"
+                        )),
+                    Arg::with_name("game")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .help("ul.cfg index, OPL name or serial of game"),
+                    Arg::with_name("mode")
+                        .long("mode")
+                        .takes_value(true)
+                        .help(concat!(
+                            "Comma separated compatibility modes, e.g. 1,3\n",
+                            "Use none to disable all of them"
+                        )),
+                    Arg::with_name("vmc")
+                        .long("vmc")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(concat!(
+                            "Virtual memory card for slot, e.g. slot0=NAME\n",
+                            "Leave name empty to remove it from slot"
+                        )),
+                    Arg::with_name("set")
+                        .long("set")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(concat!(
+                            "Raw OPL setting, e.g. $EnableGSM=1\n",
+                            "Leave value empty to remove the setting"
+                        )),
                 ]),
         )
         .subcommand(
@@ -77,6 +341,187 @@ fn main() {
                         )),
                 ]),
         )
+        .subcommand(
+            App::new("export")
+                .about(concat!(
+                    "Reassembles games from ul. chunks into OPL DVD/ and CD/ folders\n",
+                    "as SERIAL.Name.iso, e.g. for exFAT drives or SMB shares"
+                ))
+                .args(&[
+                    Arg::with_name("ulpath")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help(concat!(
+                            "Directory from which games should be exported\n",
+                            "Must contain valid ul.cfg file"
+                        )),
+                    Arg::with_name("target")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .help("OPL root directory where DVD/ and CD/ folders are created"),
+                    Arg::with_name("index")
+                        .conflicts_with("name")
+                        .short("i")
+                        .takes_value(true)
+                        .help(concat!(
+                            "ul.cfg index of game that is to be exported\n",
+                            "If neither index nor name is given, all games are exported"
+                        )),
+                    Arg::with_name("name")
+                        .conflicts_with("index")
+                        .short("n")
+                        .takes_value(true)
+                        .help("OPL name of game that is to be exported"),
+                ]),
+        )
+        .subcommand(
+            App::new("hdd")
+                .about(concat!(
+                    "Manages HD Loader games on PlayStation 2 internal hard drive\n",
+                    "image with APA partitions, similarly to hdl_dump"
+                ))
+                .subcommand(
+                    App::new("init")
+                        .about("Creates new APA formatted disk image")
+                        .args(&[
+                            Arg::with_name("image")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path where disk image should be created"),
+                            Arg::with_name("size")
+                                .long("size")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Size of disk image in gigabytes"),
+                        ]),
+                )
+                .subcommand(
+                    App::new("install")
+                        .about(concat!(
+                            "Creates HD Loader partition on disk image\n",
+                            "and copies PlayStation 2 game into it"
+                        ))
+                        .args(&[
+                            Arg::with_name("image")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path to APA formatted disk image"),
+                            Arg::with_name("iso")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Path to PlayStation 2 .iso, .cso, .zso or .chd disk image\n",
+                                    "CD games can also be installed from .cue or raw .bin dump\n",
+                                    "Archives and stdin are not accepted, extract image first"
+                                )),
+                            Arg::with_name("name")
+                                .short("n")
+                                .takes_value(true)
+                                .help(concat!(
+                                    "Name under which game will be visible in OPL\n",
+                                    "Must be <= 32 characters\n",
+                                    "If not specified, image name is taken"
+                                )),
+                        ]),
+                )
+                .subcommand(
+                    App::new("list")
+                        .about("Lists HD Loader games installed on disk image")
+                        .arg(
+                            Arg::with_name("image")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path to APA formatted disk image"),
+                        ),
+                )
+                .subcommand(
+                    App::new("extract")
+                        .about(concat!(
+                            "Copies HD Loader game out of disk image\n",
+                            "into plain .iso or USBAdvance/Extreme format"
+                        ))
+                        .args(&[
+                            Arg::with_name("image")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path to APA formatted disk image"),
+                            Arg::with_name("target")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Path where .iso should be written\n",
+                                    "With --ul, directory where game should be created"
+                                )),
+                            Arg::with_name("index")
+                                .conflicts_with("name")
+                                .required_unless("name")
+                                .short("i")
+                                .takes_value(true)
+                                .help("Index of game as shown by `ulmake hdd list`"),
+                            Arg::with_name("name")
+                                .conflicts_with("index")
+                                .required_unless("index")
+                                .short("n")
+                                .takes_value(true)
+                                .help("HD Loader name of game that is to be extracted"),
+                            Arg::with_name("ul").long("ul").help(concat!(
+                                "Creates game in USBAdvance/Extreme format and registers\n",
+                                "it in ul.cfg instead of writing .iso"
+                            )),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new("import")
+                .about(concat!(
+                    "Creates USBAdvance/Extreme format games out of OPL DVD/ and CD/\n",
+                    "folders with SERIAL.Title.iso images and registers them in ul.cfg"
+                ))
+                .args(&[
+                    Arg::with_name("oplroot")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("OPL root directory containing DVD/ and/or CD/ folders"),
+                    Arg::with_name("ulpath")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .help(concat!(
+                            "Directory where games should be created\n",
+                            "If ul.cfg is not found there, new one will be created"
+                        )),
+                ]),
+        )
+        .subcommand(
+            App::new("info")
+                .about(concat!(
+                    "Shows chunk files, serial, region, video mode, volume label\n",
+                    "and boot executable of game in ul.cfg or of disk image"
+                ))
+                .args(&[
+                    Arg::with_name("path")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help(concat!(
+                            "Directory containing ul.cfg file\n",
+                            "or path to disk image in any format `ulmake add` accepts"
+                        )),
+                    Arg::with_name("game")
+                        .index(2)
+                        .takes_value(true)
+                        .help("ul.cfg index, OPL name or serial of game"),
+                ]),
+        )
         .subcommand(
             App::new("list")
                 .about("Lists current entries in ul.cfg")
@@ -88,14 +533,163 @@ fn main() {
                             "Directory containing ul.cfg file\n",
                             "Defaults to current dir if not specified"
                         )),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .help("Also shows OPL per-game settings from CFG/ folder"),
+                )
+                .arg(
+                    Arg::with_name("titles")
+                        .long("titles")
+                        .takes_value(true)
+                        .help(concat!(
+                            "Offline title database, either PCSX2 GameIndex.yaml\n",
+                            "or CSV with serial,title[,region] lines\n",
+                            "Adds column with canonical title of each game"
+                        )),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .default_value("table")
+                        .possible_values(&["table", "json", "csv", "tsv"])
+                        .help(concat!(
+                            "Output format, json, csv and tsv print only games with\n",
+                            "index, name, serial, crc, chunk_count, size (bytes),\n",
+                            "status, media and chunks (paths, ; separated in csv and tsv)"
+                        )),
+                ),
+        )
+        .subcommand(
+            App::new("vmc")
+                .about("Manages PlayStation 2 virtual memory cards used by OPL")
+                .subcommand(
+                    App::new("create")
+                        .about("Creates formatted virtual memory card in VMC/ folder")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory where VMC/ folder should be created"),
+                            Arg::with_name("name")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Name of memory card, saved as VMC/NAME.bin\n",
+                                    "Must be <= 32 characters"
+                                )),
+                            Arg::with_name("size")
+                                .long("size")
+                                .takes_value(true)
+                                .default_value("8")
+                                .possible_values(&["8", "16", "32", "64"])
+                                .help("Size of memory card in megabytes"),
+                            Arg::with_name("ecc").long("ecc").help(concat!(
+                                "Appends ECC after every page, like raw dumps\n",
+                                "and PCSX2 cards do (OPL uses cards without it)"
+                            )),
+                            Arg::with_name("game")
+                                .long("game")
+                                .takes_value(true)
+                                .help(concat!(
+                                    "ul.cfg index, OPL name or serial of game\n",
+                                    "which should use the card"
+                                )),
+                            Arg::with_name("slot")
+                                .long("slot")
+                                .takes_value(true)
+                                .default_value("0")
+                                .possible_values(&["0", "1"])
+                                .help("Memory card slot used with --game"),
+                        ]),
+                )
+                .subcommand(
+                    App::new("ls")
+                        .about("Lists save folders on virtual memory card")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing VMC/ folder"),
+                            Arg::with_name("name")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Name of memory card in VMC/ folder"),
+                        ]),
+                )
+                .subcommand(
+                    App::new("import")
+                        .about("Copies save folder from .psu, .max or .cbs file onto memory card")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing VMC/ folder"),
+                            Arg::with_name("name")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Name of memory card in VMC/ folder"),
+                            Arg::with_name("save")
+                                .index(3)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Path to EMS .psu, Action Replay Max .max or CodeBreaker .cbs save"),
+                        ]),
+                )
+                .subcommand(
+                    App::new("export")
+                        .about("Saves folder from memory card as .psu or .max file")
+                        .args(&[
+                            Arg::with_name("ulpath")
+                                .index(1)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Directory containing VMC/ folder"),
+                            Arg::with_name("name")
+                                .index(2)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Name of memory card in VMC/ folder"),
+                            Arg::with_name("folder")
+                                .index(3)
+                                .takes_value(true)
+                                .required(true)
+                                .help("Save folder name, as shown by `ulmake vmc ls`"),
+                            Arg::with_name("target")
+                                .index(4)
+                                .takes_value(true)
+                                .required(true)
+                                .help(concat!(
+                                    "Path of save file to be written\n",
+                                    ".max extension selects Action Replay Max format, .psu otherwise"
+                                )),
+                        ]),
                 ),
         )
         .get_matches();
 
     match matches.subcommand() {
         ("add", Some(args)) => commands::add::add(args),
+        ("apps", Some(args)) => commands::apps::apps(args),
+        ("art", Some(args)) => commands::art::art(args),
+        ("cheats", Some(args)) => commands::cheats::cheats(args),
+        ("config", Some(args)) => commands::config::config(args),
         ("delete", Some(args)) => commands::delete::delete(args),
+        ("export", Some(args)) => commands::export::export(args),
+        ("hdd", Some(args)) => commands::hdd::hdd(args),
+        ("import", Some(args)) => commands::import::import(args),
+        ("info", Some(args)) => commands::info::info(args),
         ("list", Some(args)) => commands::list::list(args),
+        ("vmc", Some(args)) => commands::vmc::vmc(args),
         _ => (),
     }
 }
@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current time as PS2 `ps2time` (padding, sec, min, hour, day, month, year).
+pub fn now() -> [u8; 8] {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    // Days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time = secs % 86400;
+    let year = (year as u16).to_le_bytes();
+    [
+        0x00,
+        (time % 60) as u8,
+        (time / 60 % 60) as u8,
+        (time / 3600) as u8,
+        day as u8,
+        month as u8,
+        year[0],
+        year[1],
+    ]
+}
@@ -0,0 +1,67 @@
+use std::env::var_os;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+// Settings live in ulmake.cfg of user config directory,
+// unless ULMAKE_CONFIG points elsewhere.
+const SETTINGS_ENV: &str = "ULMAKE_CONFIG";
+const SETTINGS_DIR: &str = "ulmake";
+const SETTINGS_FILE: &str = "ulmake.cfg";
+const SETTINGS_NAME_TEMPLATE: &str = "name_template";
+
+/// User defaults for ulmake, kept as `key=value` lines.
+#[derive(Default)]
+pub struct Settings {
+    /// Template `add` names games with when `-n` is not given.
+    pub name_template: Option<String>,
+}
+
+fn settings_path() -> Option<PathBuf> {
+    if let Some(path) = var_os(SETTINGS_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+
+    Some(config_dir.join(SETTINGS_DIR).join(SETTINGS_FILE))
+}
+
+impl Settings {
+    /// Loads user settings, falling back to defaults if there are none.
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|p| read_to_string(p).ok())
+            .map(|c| Settings::parse(&c))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut settings = Settings::default();
+
+        for (key, value) in contents.lines().filter_map(|l| l.split_once('=')) {
+            if key.trim() == SETTINGS_NAME_TEMPLATE && !value.trim().is_empty() {
+                settings.name_template = Some(String::from(value.trim()));
+            }
+        }
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::parse("# defaults\nname_template = {title} [{region}]\n");
+        assert_eq!(
+            settings.name_template.as_deref(),
+            Some("{title} [{region}]")
+        );
+        assert!(Settings::parse("name_template=\n").name_template.is_none());
+    }
+}
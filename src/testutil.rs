@@ -0,0 +1,34 @@
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Scratch directory of one test, removed when dropped.
+///
+/// Names carry process id, so aborted or concurrent runs do not collide.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let path = temp_dir().join(format!("ulmake_{}_{}", process::id(), name));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        TestDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}
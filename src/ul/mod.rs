@@ -1,8 +1,14 @@
 mod parser;
+pub mod record;
 mod status;
-mod table;
+pub mod table;
 
+use crate::game::art::{art_flags, ART_KINDS};
+use crate::game::media::MediaType;
+use crate::game::source::stream::StreamImage;
+use crate::game::titles::TitleDb;
 use crate::game::Game;
+use crate::ul::record::GameRecord;
 use crate::ul::status::GameStatus;
 
 use std::fs::{write, File};
@@ -13,10 +19,10 @@ use std::path::Path;
 const UL_GAME_SIZE: usize = 64;
 const UL_GAME_NAME_SIZE: usize = 32;
 const UL_GAME_CHUNK_COUNT: usize = 47;
+const UL_GAME_MEDIA_TYPE: usize = 48;
 const UL_SERIAL_SIZE: usize = 12;
 const UL_EMPTY_SIZE: usize = 4;
 const UL_NAME_EXT_SIZE: usize = 10;
-const SCEC_DVD_MEDIA_TYPE: u8 = 0x14;
 const USBEXTREME_MAGIC: u8 = 0x08;
 
 macro_rules! strvec {
@@ -50,7 +56,8 @@ impl Ulcfg {
 
             file = handle.into_inner();
             let opl_name = parser::parse_to_string(&buffer, 0, UL_GAME_NAME_SIZE);
-            let game = Game::from_config(path.parent().unwrap(), opl_name);
+            let media = MediaType::from(buffer[UL_GAME_MEDIA_TYPE]);
+            let game = Game::from_config(path.parent().unwrap(), opl_name, media);
 
             let mut state = GameStatus::Good;
             if game.num_chunks() == 0 {
@@ -82,8 +89,8 @@ impl Ulcfg {
             // next byte is number of game chunks
             ulbuff.push(entry.num_chunks());
 
-            // last 16 bytes are just constants
-            ulbuff.push(SCEC_DVD_MEDIA_TYPE);
+            // next byte is media type and last 15 bytes are just constants
+            ulbuff.push(u8::from(entry.media()));
             ulbuff.extend_from_slice(&[0x00; UL_EMPTY_SIZE]);
             ulbuff.push(USBEXTREME_MAGIC);
             ulbuff.extend_from_slice(&[0x00; UL_NAME_EXT_SIZE]);
@@ -93,9 +100,59 @@ impl Ulcfg {
         Ok(())
     }
 
-    pub fn list_games(&self) {
-        let col_names = strvec!["Index", "Name", "Serial", "Size", "Status"];
-        let col_sizes = vec![5, UL_GAME_NAME_SIZE, UL_SERIAL_SIZE, 6, 10];
+    /// Describes every game, in ul.cfg order.
+    pub fn records(&self) -> Vec<GameRecord> {
+        let it = self.games.iter().zip(self.states.iter());
+        it.enumerate()
+            .map(|(index, (game, state))| GameRecord {
+                index,
+                name: String::from(&game.opl_name),
+                serial: game.read_serial().ok(),
+                crc: String::from(game.crc_name()),
+                size: game.size(),
+                status: state.to_string(),
+                media: game.media().to_string(),
+                chunks: game
+                    .chunk_files()
+                    .iter()
+                    .map(|(p, _)| p.display().to_string())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Prints games as table, with art found in `ulpath` directory
+    /// and database titles if `titles` are given.
+    pub fn list_games(&self, ulpath: &Path, titles: Option<&TitleDb>) {
+        let mut col_names = strvec!["Index", "Name", "Serial", "Size", "Status", "Art"];
+        let mut col_sizes = vec![5, UL_GAME_NAME_SIZE, UL_SERIAL_SIZE, 6, 10, ART_KINDS.len()];
+
+        let records = self.records();
+        let serials = records
+            .iter()
+            .map(|r| {
+                r.serial
+                    .clone()
+                    .unwrap_or_else(|| String::from("NOT FOUND"))
+            })
+            .collect::<Vec<String>>();
+
+        let db_titles = serials
+            .iter()
+            .map(|s| {
+                titles
+                    .and_then(|t| t.get(s))
+                    .map(|e| e.title.clone())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<String>>();
+
+        if titles.is_some() {
+            let longest = db_titles.iter().map(|t| t.len()).max().unwrap_or(0);
+            col_names.push(String::from("Title"));
+            col_sizes.push(longest.max(5));
+        }
+
         let hline = table::make_hline(&col_sizes);
         let header = table::make_row(col_names, &col_sizes);
 
@@ -103,15 +160,19 @@ impl Ulcfg {
         println!("{}", header);
         println!("{}", hline);
 
-        let it = self.games.iter().zip(self.states.iter());
-        for (pos, (game, state)) in it.enumerate() {
-            let contents = vec![
-                pos.to_string(),
-                String::from(&game.opl_name),
-                game.serial(),
-                game.formatted_size(),
-                format!("{}", state),
+        for (record, serial) in records.iter().zip(serials.iter()) {
+            let mut contents = vec![
+                record.index.to_string(),
+                record.name.clone(),
+                serial.clone(),
+                format!("{:.2}GB", record.size as f64 / 1_000_000_000.0),
+                record.status.clone(),
+                art_flags(ulpath, serial),
             ];
+            if titles.is_some() {
+                contents.push(db_titles[record.index].clone());
+            }
+
             let row = table::make_row(contents, &col_sizes);
             println!("{}", row);
         }
@@ -124,10 +185,95 @@ impl Ulcfg {
         // TODO Cleanup if create_chunks failed?
         game.create_chunks(dstpath)?;
         self.games.push(game);
+        self.states.push(GameStatus::Good);
 
         Ok(())
     }
 
+    pub fn import_game(
+        &mut self,
+        isopath: &Path,
+        dstpath: &Path,
+        opl_name: String,
+        media: MediaType,
+    ) -> Result<()> {
+        let mut game = Game::from_iso(isopath, opl_name);
+        game.create_chunks(dstpath)?;
+        // OPL folder the image was taken from is what decides its media type.
+        game.set_media(media);
+        self.games.push(game);
+        self.states.push(GameStatus::Good);
+
+        Ok(())
+    }
+
+    pub fn add_game_from_stream(
+        &mut self,
+        stream: StreamImage,
+        dstpath: &Path,
+        opl_name: String,
+    ) -> Result<()> {
+        let mut game = Game::from_stream(opl_name);
+        game.create_chunks_from_stream(stream, dstpath)?;
+        self.games.push(game);
+        self.states.push(GameStatus::Good);
+
+        Ok(())
+    }
+
+    pub fn find_game(&self, name: &str) -> Option<usize> {
+        self.games.iter().position(|g| g.opl_name == name)
+    }
+
+    /// Finds game by ul.cfg index, OPL name or serial, in that order.
+    pub fn select_game(&self, game: &str) -> Option<usize> {
+        match game.parse::<usize>() {
+            Ok(index) if index < self.games.len() => Some(index),
+            _ => self
+                .find_game(game)
+                .or_else(|| self.games.iter().position(|g| g.serial() == game)),
+        }
+    }
+
+    pub fn game(&self, index: usize) -> Option<&Game> {
+        self.games.get(index)
+    }
+
+    pub fn game_name(&self, index: usize) -> Option<&str> {
+        self.games.get(index).map(|g| g.opl_name.as_str())
+    }
+
+    pub fn game_serial(&self, index: usize) -> Result<String> {
+        let game = self.games.get(index).ok_or(ErrorKind::InvalidInput)?;
+        game.read_serial()
+    }
+
+    pub fn game_size(&self, index: usize) -> u64 {
+        self.games.get(index).map(|g| g.size()).unwrap_or(0)
+    }
+
+    pub fn num_games(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn export_game(&self, index: usize, target: &Path) -> Result<()> {
+        let game = self.games.get(index).ok_or(ErrorKind::InvalidInput)?;
+
+        if let GameStatus::Bad(reason) = &self.states[index] {
+            // Missing chunks would leave a truncated image behind.
+            println!("Skipping {}: {}", &game.opl_name, reason);
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        match game.export_chunks(target) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                println!("Could not export {}", &game.opl_name);
+                Err(e)
+            }
+        }
+    }
+
     pub fn delete_game_by_name(&mut self, name: &str) -> Result<()> {
         for (index, game) in self.games.iter().enumerate() {
             if game.opl_name == name {
@@ -0,0 +1,204 @@
+use std::io::{Error, ErrorKind, Result};
+
+const CSV_HEADER: [&str; 9] = [
+    "index",
+    "name",
+    "serial",
+    "crc",
+    "chunk_count",
+    "size",
+    "status",
+    "media",
+    "chunks",
+];
+// Chunk paths share one field in CSV and TSV.
+const CHUNK_SEPARATOR: &str = ";";
+
+/// Everything `list` tells about one game, before it is rendered.
+pub struct GameRecord {
+    pub index: usize,
+    pub name: String,
+    /// Serial from chunk names, `None` when chunks are missing.
+    pub serial: Option<String>,
+    pub crc: String,
+    pub size: u64,
+    pub status: String,
+    pub media: String,
+    pub chunks: Vec<String>,
+}
+
+/// Output format of `list`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl std::str::FromStr for ListFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "table" => Ok(ListFormat::Table),
+            "json" => Ok(ListFormat::Json),
+            "csv" => Ok(ListFormat::Csv),
+            "tsv" => Ok(ListFormat::Tsv),
+            _ => Err(Error::from(ErrorKind::InvalidInput)),
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Renders `records` as JSON array with one object per game.
+pub fn to_json(records: &[GameRecord]) -> String {
+    let objects = records
+        .iter()
+        .map(|r| {
+            let chunks = r
+                .chunks
+                .iter()
+                .map(|c| json_string(c))
+                .collect::<Vec<String>>();
+            let serial = r
+                .serial
+                .as_deref()
+                .map_or(String::from("null"), json_string);
+
+            format!(
+                concat!(
+                    "  {{\"index\": {}, \"name\": {}, \"serial\": {}, \"crc\": {}, ",
+                    "\"chunk_count\": {}, \"size\": {}, \"status\": {}, ",
+                    "\"media\": {}, \"chunks\": [{}]}}"
+                ),
+                r.index,
+                json_string(&r.name),
+                serial,
+                json_string(&r.crc),
+                r.chunks.len(),
+                r.size,
+                json_string(&r.status),
+                json_string(&r.media),
+                chunks.join(", ")
+            )
+        })
+        .collect::<Vec<String>>();
+
+    match objects.is_empty() {
+        true => String::from("[]"),
+        false => format!("[\n{}\n]", objects.join(",\n")),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => String::from(text),
+    }
+}
+
+fn tsv_field(text: &str) -> String {
+    // TSV has no quoting, so characters that would break the row go.
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Renders `records` as CSV or TSV with header line.
+pub fn to_delimited(records: &[GameRecord], format: ListFormat) -> String {
+    let (separator, field): (&str, fn(&str) -> String) = match format {
+        ListFormat::Tsv => ("\t", tsv_field),
+        _ => (",", csv_field),
+    };
+
+    let mut lines = vec![CSV_HEADER.join(separator)];
+    for r in records {
+        let fields = [
+            r.index.to_string(),
+            r.name.clone(),
+            r.serial.clone().unwrap_or_default(),
+            r.crc.clone(),
+            r.chunks.len().to_string(),
+            r.size.to_string(),
+            r.status.clone(),
+            r.media.clone(),
+            r.chunks.join(CHUNK_SEPARATOR),
+        ];
+        let fields = fields.iter().map(|f| field(f)).collect::<Vec<String>>();
+        lines.push(fields.join(separator));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str) -> GameRecord {
+        GameRecord {
+            index: 0,
+            name: String::from(name),
+            serial: Some(String::from("SLUS_200.62")),
+            crc: String::from("84BA9D95"),
+            size: 2048,
+            status: String::from("OK"),
+            media: String::from("DVD"),
+            chunks: vec![
+                String::from("ul.84BA9D95.SLUS_200.62.00"),
+                String::from("ul.84BA9D95.SLUS_200.62.01"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = to_json(&[record("Say \"Hi\"\\")]);
+        assert!(json.contains("\"name\": \"Say \\\"Hi\\\"\\\\\""));
+        assert!(json.contains("\"chunk_count\": 2, \"size\": 2048"));
+        assert!(json.contains("\"chunks\": [\"ul.84BA9D95.SLUS_200.62.00\", "));
+        assert_eq!(to_json(&[]), "[]");
+    }
+
+    #[test]
+    fn test_to_delimited() {
+        let csv = to_delimited(&[record("Foo, Bar")], ListFormat::Csv);
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            lines[0],
+            "index,name,serial,crc,chunk_count,size,status,media,chunks"
+        );
+        assert_eq!(
+            lines[1],
+            concat!(
+                "0,\"Foo, Bar\",SLUS_200.62,84BA9D95,2,2048,OK,DVD,",
+                "ul.84BA9D95.SLUS_200.62.00;ul.84BA9D95.SLUS_200.62.01"
+            )
+        );
+
+        let tsv = to_delimited(&[record("Foo\tBar")], ListFormat::Tsv);
+        assert!(tsv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("0\tFoo Bar\tSLUS_200.62\t"));
+        assert!("xml".parse::<ListFormat>().is_err());
+    }
+}
@@ -0,0 +1,49 @@
+// Hamming code used by PS2 memory cards covers data in 128 byte chunks.
+pub const ECC_CHUNK_SIZE: usize = 128;
+const COLUMN_PARITY_MASKS: [u8; 7] = [0x55, 0x33, 0x0f, 0x00, 0xaa, 0xcc, 0xf0];
+
+fn parity(byte: u8) -> u8 {
+    (byte.count_ones() & 1) as u8
+}
+
+fn column_parity_mask(byte: u8) -> u8 {
+    COLUMN_PARITY_MASKS
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, m)| acc | parity(byte & m) << i)
+}
+
+/// Three ECC bytes (column parity and two line parities) of 128 byte `chunk`.
+pub fn calculate(chunk: &[u8]) -> [u8; 3] {
+    let mut column_parity = 0x77;
+    let mut line_parity_0 = 0x7f;
+    let mut line_parity_1 = 0x7f;
+
+    for (i, byte) in chunk.iter().enumerate() {
+        column_parity ^= column_parity_mask(*byte);
+        if parity(*byte) == 1 {
+            line_parity_0 ^= !(i as u8);
+            line_parity_1 ^= i as u8;
+        }
+    }
+
+    [column_parity, line_parity_0 & 0x7f, line_parity_1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ecc_erased_chunk() {
+        assert_eq!(calculate(&[0x00; ECC_CHUNK_SIZE]), [0x77, 0x7f, 0x7f]);
+        assert_eq!(calculate(&[0xff; ECC_CHUNK_SIZE]), [0x77, 0x7f, 0x7f]);
+    }
+
+    #[test]
+    fn test_ecc_single_bit() {
+        let mut chunk = [0x00; ECC_CHUNK_SIZE];
+        chunk[0] = 0x01;
+        assert_eq!(calculate(&chunk), [0x70, 0x00, 0x7f]);
+    }
+}
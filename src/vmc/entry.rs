@@ -0,0 +1,133 @@
+use crate::ps2time;
+
+use std::io::{Error, ErrorKind, Result};
+
+pub const ENTRY_SIZE: usize = 512;
+const ENTRY_NAME_SIZE: usize = 32;
+
+const MODE_READ: u16 = 0x0001;
+const MODE_WRITE: u16 = 0x0002;
+const MODE_EXECUTE: u16 = 0x0004;
+const MODE_FILE: u16 = 0x0010;
+const MODE_DIRECTORY: u16 = 0x0020;
+const MODE_0080: u16 = 0x0080;
+const MODE_0400: u16 = 0x0400;
+const MODE_HIDDEN: u16 = 0x2000;
+pub const MODE_EXISTS: u16 = 0x8000;
+
+// Modes of folders, their `..` links and files, as the PS2 creates them.
+pub const DIR_MODE: u16 =
+    MODE_READ | MODE_WRITE | MODE_EXECUTE | MODE_DIRECTORY | MODE_0400 | MODE_EXISTS;
+pub const PARENT_MODE: u16 =
+    MODE_WRITE | MODE_EXECUTE | MODE_DIRECTORY | MODE_0400 | MODE_HIDDEN | MODE_EXISTS;
+pub const FILE_MODE: u16 =
+    MODE_READ | MODE_WRITE | MODE_EXECUTE | MODE_FILE | MODE_0080 | MODE_0400 | MODE_EXISTS;
+
+const ENTRY_MODE: usize = 0x000;
+const ENTRY_LENGTH: usize = 0x004;
+const ENTRY_CREATED: usize = 0x008;
+const ENTRY_CLUSTER: usize = 0x010;
+const ENTRY_DIR_ENTRY: usize = 0x014;
+const ENTRY_MODIFIED: usize = 0x018;
+const ENTRY_NAME: usize = 0x040;
+
+/// File or directory entry, one per memory card page.
+#[derive(Clone)]
+pub struct DirEntry {
+    pub mode: u16,
+    /// Size in bytes for files, number of entries for directories.
+    pub length: u32,
+    pub created: [u8; 8],
+    pub cluster: u32,
+    /// Index of directory in its parent, set in `.` entries only.
+    pub dir_entry: u32,
+    pub modified: [u8; 8],
+    pub name: String,
+}
+
+impl DirEntry {
+    pub fn new(name: &str, mode: u16, length: u32, cluster: u32) -> Self {
+        let now = ps2time::now();
+        DirEntry {
+            mode,
+            length,
+            created: now,
+            cluster,
+            dir_entry: 0,
+            modified: now,
+            name: String::from(name),
+        }
+    }
+
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < ENTRY_SIZE {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let get_u32 = |offset: usize| {
+            let mut bytes = [0x00; 4];
+            bytes.copy_from_slice(&buffer[offset..offset + 4]);
+            u32::from_le_bytes(bytes)
+        };
+
+        let mut created = [0x00; 8];
+        let mut modified = [0x00; 8];
+        created.copy_from_slice(&buffer[ENTRY_CREATED..ENTRY_CREATED + 8]);
+        modified.copy_from_slice(&buffer[ENTRY_MODIFIED..ENTRY_MODIFIED + 8]);
+
+        let name = &buffer[ENTRY_NAME..ENTRY_NAME + ENTRY_NAME_SIZE];
+        let end = name
+            .iter()
+            .position(|b| *b == 0x00)
+            .unwrap_or(ENTRY_NAME_SIZE);
+
+        Ok(DirEntry {
+            mode: u16::from_le_bytes([buffer[ENTRY_MODE], buffer[ENTRY_MODE + 1]]),
+            length: get_u32(ENTRY_LENGTH),
+            created,
+            cluster: get_u32(ENTRY_CLUSTER),
+            dir_entry: get_u32(ENTRY_DIR_ENTRY),
+            modified,
+            name: String::from_utf8_lossy(&name[..end]).to_string(),
+        })
+    }
+
+    pub fn exists(&self) -> bool {
+        self.mode & MODE_EXISTS != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & MODE_DIRECTORY != 0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = vec![0x00; ENTRY_SIZE];
+        let name = &self.name.as_bytes()[..self.name.len().min(ENTRY_NAME_SIZE)];
+
+        raw[ENTRY_MODE..ENTRY_MODE + 2].copy_from_slice(&self.mode.to_le_bytes());
+        raw[ENTRY_LENGTH..ENTRY_LENGTH + 4].copy_from_slice(&self.length.to_le_bytes());
+        raw[ENTRY_CREATED..ENTRY_CREATED + 8].copy_from_slice(&self.created);
+        raw[ENTRY_CLUSTER..ENTRY_CLUSTER + 4].copy_from_slice(&self.cluster.to_le_bytes());
+        raw[ENTRY_DIR_ENTRY..ENTRY_DIR_ENTRY + 4].copy_from_slice(&self.dir_entry.to_le_bytes());
+        raw[ENTRY_MODIFIED..ENTRY_MODIFIED + 8].copy_from_slice(&self.modified);
+        raw[ENTRY_NAME..ENTRY_NAME + name.len()].copy_from_slice(name);
+
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let mut entry = DirEntry::new("BESLES-12345", DIR_MODE, 3, 7);
+        entry.dir_entry = 2;
+
+        let parsed = DirEntry::parse(&entry.to_bytes()).unwrap();
+        assert_eq!(parsed.name, "BESLES-12345");
+        assert_eq!((parsed.length, parsed.cluster, parsed.dir_entry), (3, 7, 2));
+        assert!(parsed.exists() && parsed.is_dir());
+    }
+}
@@ -0,0 +1,420 @@
+use std::io::{Error, ErrorKind, Result};
+
+// LZARI by Haruhiko Okumura, the compression used by Action Replay Max saves.
+// LZSS with sliding window, whose literals, match lengths and match
+// positions are written with adaptive arithmetic coding.
+const N: usize = 4096;
+const F: usize = 60;
+const THRESHOLD: usize = 2;
+const N_CHAR: usize = 256 - THRESHOLD + F;
+
+const M: u32 = 15;
+const Q1: u64 = 1 << M;
+const Q2: u64 = 2 * Q1;
+const Q3: u64 = 3 * Q1;
+const Q4: u64 = 4 * Q1;
+const MAX_CUM: u32 = (Q1 - 1) as u32;
+// Candidates checked per position when looking for a match.
+const MAX_CHAIN: usize = 256;
+// Decoder looks ahead of encoded bits, anything further means input ran out.
+const MAX_EXTRA_BITS: usize = 64;
+
+/// Adaptive frequency model shared by encoder and decoder.
+struct Model {
+    char_to_sym: Vec<usize>,
+    sym_to_char: Vec<usize>,
+    sym_freq: Vec<u32>,
+    sym_cum: Vec<u32>,
+    position_cum: Vec<u32>,
+}
+
+impl Model {
+    fn new() -> Self {
+        let mut model = Model {
+            char_to_sym: vec![0; N_CHAR],
+            sym_to_char: vec![0; N_CHAR + 1],
+            sym_freq: vec![0; N_CHAR + 1],
+            sym_cum: vec![0; N_CHAR + 1],
+            position_cum: vec![0; N + 1],
+        };
+
+        for sym in (1..=N_CHAR).rev() {
+            model.char_to_sym[sym - 1] = sym;
+            model.sym_to_char[sym] = sym - 1;
+            model.sym_freq[sym] = 1;
+            model.sym_cum[sym - 1] = model.sym_cum[sym] + 1;
+        }
+
+        // Nearer positions are assumed to be more likely.
+        for i in (1..=N).rev() {
+            model.position_cum[i - 1] = model.position_cum[i] + 10000 / (i as u32 + 200);
+        }
+
+        model
+    }
+
+    fn update(&mut self, sym: usize) {
+        if self.sym_cum[0] >= MAX_CUM {
+            let mut cum = 0;
+            for i in (1..=N_CHAR).rev() {
+                self.sym_cum[i] = cum;
+                self.sym_freq[i] = (self.sym_freq[i] + 1) >> 1;
+                cum += self.sym_freq[i];
+            }
+            self.sym_cum[0] = cum;
+        }
+
+        let mut i = sym;
+        while self.sym_freq[i] == self.sym_freq[i - 1] {
+            i -= 1;
+        }
+
+        if i < sym {
+            let ch_i = self.sym_to_char[i];
+            let ch_sym = self.sym_to_char[sym];
+            self.sym_to_char[i] = ch_sym;
+            self.sym_to_char[sym] = ch_i;
+            self.char_to_sym[ch_i] = sym;
+            self.char_to_sym[ch_sym] = i;
+        }
+
+        self.sym_freq[i] += 1;
+        for cum in &mut self.sym_cum[..i] {
+            *cum += 1;
+        }
+    }
+
+    fn search_sym(&self, x: u32) -> usize {
+        let (mut i, mut j) = (1, N_CHAR);
+        while i < j {
+            let k = (i + j) / 2;
+            if self.sym_cum[k] > x {
+                i = k + 1;
+            } else {
+                j = k;
+            }
+        }
+        i
+    }
+
+    fn search_position(&self, x: u32) -> usize {
+        let (mut i, mut j) = (1, N);
+        while i < j {
+            let k = (i + j) / 2;
+            if self.position_cum[k] > x {
+                i = k + 1;
+            } else {
+                j = k;
+            }
+        }
+        i - 1
+    }
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    bit: usize,
+    low: u64,
+    high: u64,
+    value: u64,
+    model: Model,
+}
+
+impl Decoder<'_> {
+    fn next_bit(&mut self) -> u64 {
+        // Encoder flushes enough bits, reading past the end yields zeros.
+        let byte = self.input.get(self.bit / 8).copied().unwrap_or(0);
+        let bit = (byte >> (7 - self.bit % 8)) & 1;
+        self.bit += 1;
+        bit as u64
+    }
+
+    fn narrow(&mut self, low_cum: u32, high_cum: u32, total: u32) {
+        let range = self.high - self.low;
+        self.high = self.low + range * high_cum as u64 / total as u64;
+        self.low += range * low_cum as u64 / total as u64;
+
+        loop {
+            if self.low >= Q2 {
+                self.value -= Q2;
+                self.low -= Q2;
+                self.high -= Q2;
+            } else if self.low >= Q1 && self.high <= Q3 {
+                self.value -= Q1;
+                self.low -= Q1;
+                self.high -= Q1;
+            } else if self.high > Q2 {
+                break;
+            }
+
+            self.low += self.low;
+            self.high += self.high;
+            self.value = 2 * self.value + self.next_bit();
+        }
+    }
+
+    fn target(&self, total: u32) -> u32 {
+        let range = self.high - self.low;
+        (((self.value - self.low + 1) * total as u64 - 1) / range) as u32
+    }
+
+    fn decode_char(&mut self) -> usize {
+        let total = self.model.sym_cum[0];
+        let sym = self.model.search_sym(self.target(total));
+        let (low, high) = (self.model.sym_cum[sym], self.model.sym_cum[sym - 1]);
+        self.narrow(low, high, total);
+
+        let ch = self.model.sym_to_char[sym];
+        self.model.update(sym);
+        ch
+    }
+
+    fn decode_position(&mut self) -> usize {
+        let total = self.model.position_cum[0];
+        let position = self.model.search_position(self.target(total));
+        let (low, high) = (
+            self.model.position_cum[position + 1],
+            self.model.position_cum[position],
+        );
+        self.narrow(low, high, total);
+        position
+    }
+}
+
+/// Decompresses `input` into `size` bytes.
+///
+/// Fails if `input` runs out first, which corrupt size would lead to.
+pub fn decode(input: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut decoder = Decoder {
+        input,
+        bit: 0,
+        low: 0,
+        high: Q4,
+        value: 0,
+        model: Model::new(),
+    };
+
+    for _ in 0..M + 2 {
+        decoder.value = 2 * decoder.value + decoder.next_bit();
+    }
+
+    let mut text = vec![b' '; N];
+    let mut r = N - F;
+    // Size comes from save header, so it is not trusted for allocation.
+    let mut output = Vec::new();
+
+    while output.len() < size {
+        if decoder.bit > input.len() * 8 + MAX_EXTRA_BITS {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let c = decoder.decode_char();
+        if c < 256 {
+            output.push(c as u8);
+            text[r] = c as u8;
+            r = (r + 1) & (N - 1);
+            continue;
+        }
+
+        let start = (r + N - decoder.decode_position() - 1) & (N - 1);
+        for k in 0..c - 255 + THRESHOLD {
+            let byte = text[(start + k) & (N - 1)];
+            output.push(byte);
+            text[r] = byte;
+            r = (r + 1) & (N - 1);
+        }
+    }
+
+    output.truncate(size);
+    Ok(output)
+}
+
+struct Encoder {
+    output: Vec<u8>,
+    bit: usize,
+    shifter: usize,
+    low: u64,
+    high: u64,
+    model: Model,
+}
+
+impl Encoder {
+    fn put_bit(&mut self, bit: bool) {
+        if self.output.len() * 8 == self.bit {
+            self.output.push(0x00);
+        }
+
+        if bit {
+            *self.output.last_mut().unwrap() |= 0x80 >> (self.bit % 8);
+        }
+        self.bit += 1;
+    }
+
+    fn output(&mut self, bit: bool) {
+        self.put_bit(bit);
+        for _ in 0..self.shifter {
+            self.put_bit(!bit);
+        }
+        self.shifter = 0;
+    }
+
+    fn narrow(&mut self, low_cum: u32, high_cum: u32, total: u32) {
+        let range = self.high - self.low;
+        self.high = self.low + range * high_cum as u64 / total as u64;
+        self.low += range * low_cum as u64 / total as u64;
+
+        loop {
+            if self.high <= Q2 {
+                self.output(false);
+            } else if self.low >= Q2 {
+                self.output(true);
+                self.low -= Q2;
+                self.high -= Q2;
+            } else if self.low >= Q1 && self.high <= Q3 {
+                self.shifter += 1;
+                self.low -= Q1;
+                self.high -= Q1;
+            } else {
+                break;
+            }
+
+            self.low += self.low;
+            self.high += self.high;
+        }
+    }
+
+    fn encode_char(&mut self, ch: usize) {
+        let sym = self.model.char_to_sym[ch];
+        let total = self.model.sym_cum[0];
+        let (low, high) = (self.model.sym_cum[sym], self.model.sym_cum[sym - 1]);
+        self.narrow(low, high, total);
+        self.model.update(sym);
+    }
+
+    fn encode_position(&mut self, position: usize) {
+        let total = self.model.position_cum[0];
+        let (low, high) = (
+            self.model.position_cum[position + 1],
+            self.model.position_cum[position],
+        );
+        self.narrow(low, high, total);
+    }
+}
+
+/// Hash chains of 3 byte sequences seen so far, to find matches quickly.
+struct MatchFinder {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl MatchFinder {
+    fn hash(input: &[u8], pos: usize) -> Option<usize> {
+        let bytes = input.get(pos..pos + 3)?;
+        Some(((bytes[0] as usize) << 8 ^ (bytes[1] as usize) << 4 ^ bytes[2] as usize) & 0xffff)
+    }
+
+    fn insert(&mut self, input: &[u8], pos: usize) {
+        if let Some(hash) = MatchFinder::hash(input, pos) {
+            self.prev[pos] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    /// Longest earlier match for data at `pos`, as (length, distance).
+    fn find(&self, input: &[u8], pos: usize) -> (usize, usize) {
+        let max_len = F.min(input.len() - pos);
+        let mut best = (0, 0);
+        let mut candidate = match MatchFinder::hash(input, pos) {
+            Some(hash) => self.head[hash],
+            None => return best,
+        };
+
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || pos - candidate > N - F {
+                break;
+            }
+
+            let len = (0..max_len)
+                .take_while(|k| input[candidate + k] == input[pos + k])
+                .count();
+            if len > best.0 {
+                best = (len, pos - candidate);
+                if len == max_len {
+                    break;
+                }
+            }
+
+            candidate = self.prev[candidate];
+        }
+
+        best
+    }
+}
+
+/// Compresses `input`, greedily picking longest matches.
+pub fn encode(input: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder {
+        output: Vec::new(),
+        bit: 0,
+        shifter: 0,
+        low: 0,
+        high: Q4,
+        model: Model::new(),
+    };
+
+    let mut finder = MatchFinder {
+        head: vec![usize::MAX; 0x10000],
+        prev: vec![usize::MAX; input.len()],
+    };
+
+    let mut pos = 0;
+    while pos < input.len() {
+        let (len, distance) = finder.find(input, pos);
+        let len = match len > THRESHOLD {
+            true => {
+                encoder.encode_char(255 - THRESHOLD + len);
+                encoder.encode_position(distance - 1);
+                len
+            }
+            false => {
+                encoder.encode_char(input[pos] as usize);
+                1
+            }
+        };
+
+        for p in pos..pos + len {
+            finder.insert(input, p);
+        }
+        pos += len;
+    }
+
+    encoder.shifter += 1;
+    let bit = encoder.low >= Q1;
+    encoder.output(bit);
+
+    encoder.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lzari_roundtrip() {
+        let mut data = b"PS2 save data, PS2 save data, PS2 save data!".to_vec();
+        data.extend((0..5000).map(|i| (i * 7 % 251) as u8));
+        data.extend_from_slice(&[0x00; 300]);
+
+        let compressed = encode(&data);
+        assert!(compressed.len() < data.len());
+        assert!(decode(&compressed, data.len()).unwrap() == data);
+
+        let corrupt = decode(&compressed, 0xffffffff).unwrap_err();
+        assert_eq!(corrupt.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_lzari_empty() {
+        assert!(decode(&encode(&[]), 0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,535 @@
+mod ecc;
+mod entry;
+mod lzari;
+pub mod save;
+mod superblock;
+
+use crate::vmc::ecc::ECC_CHUNK_SIZE;
+use crate::vmc::entry::{DirEntry, DIR_MODE, ENTRY_SIZE, PARENT_MODE};
+use crate::vmc::save::{SaveFile, SaveFolder};
+use crate::vmc::superblock::Superblock;
+
+use std::fs::{read, File};
+use std::io::prelude::*;
+use std::io::{BufWriter, Error, ErrorKind, Result};
+use std::path::Path;
+
+const VMC_SIZES: [u64; 4] = [8, 16, 32, 64];
+const VMC_PAGE_SIZE: usize = 512;
+// Spare area after each page, holding ECC of its 128 byte chunks.
+const VMC_SPARE_SIZE: usize = 16;
+const VMC_PAGES_PER_CLUSTER: usize = 2;
+const VMC_PAGES_PER_BLOCK: usize = 16;
+const VMC_CLUSTER_SIZE: usize = VMC_PAGE_SIZE * VMC_PAGES_PER_CLUSTER;
+const VMC_ENTRIES_PER_CLUSTER: usize = VMC_CLUSTER_SIZE / ENTRY_SIZE;
+const MEGABYTE: u64 = 1024 * 1024;
+
+const FAT_ENTRY_SIZE: usize = 4;
+const FAT_ALLOCATED: u32 = 0x80000000;
+const FAT_CHAIN_END: u32 = 0xffffffff;
+const FAT_FREE: u32 = 0x7fffffff;
+
+/// PS2 memory card image, kept in memory without ECC.
+///
+/// Cards are 64MB at most, so whole filesystem is simply held in
+/// a buffer and written out at once.
+pub struct VmcImage {
+    data: Vec<u8>,
+    superblock: Superblock,
+    ecc: bool,
+}
+
+impl VmcImage {
+    /// Formats new card of `size_mb` megabytes with empty root directory.
+    ///
+    /// With `ecc` set, spare area holding ECC follows every page when saved.
+    pub fn format(size_mb: u64, ecc: bool) -> Result<Self> {
+        if !VMC_SIZES.contains(&size_mb) {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        let size = (size_mb * MEGABYTE) as usize;
+        let superblock = Superblock::new(
+            VMC_PAGE_SIZE as u16,
+            VMC_PAGES_PER_CLUSTER as u16,
+            VMC_PAGES_PER_BLOCK as u16,
+            (size / VMC_CLUSTER_SIZE) as u32,
+        );
+
+        // Erased flash reads as all ones.
+        let mut card = VmcImage {
+            data: vec![0xff; size],
+            superblock,
+            ecc,
+        };
+
+        let sb = card.superblock.to_bytes();
+        card.data[..sb.len()].copy_from_slice(&sb);
+        card.write_fat_tables();
+
+        let rootdir = card.superblock.rootdir_cluster;
+        let root = DirEntry::new(".", DIR_MODE, 2, rootdir);
+        let parent = DirEntry::new("..", PARENT_MODE, 0, rootdir);
+
+        card.set_fat(rootdir, FAT_CHAIN_END);
+        card.write_entry(&[rootdir], 0, &root);
+        card.write_entry(&[rootdir], 1, &parent);
+
+        Ok(card)
+    }
+
+    /// Opens card image, with or without ECC.
+    pub fn open(path: &Path) -> Result<Self> {
+        let raw = read(path)?;
+        let raw_page_size = VMC_PAGE_SIZE + VMC_SPARE_SIZE;
+        let is_size = |size: usize| VMC_SIZES.iter().any(|s| s * MEGABYTE == size as u64);
+
+        let (data, ecc) = match raw.len() {
+            len if is_size(len) => (raw, false),
+            len if len % raw_page_size == 0 && is_size(len / raw_page_size * VMC_PAGE_SIZE) => {
+                let data = raw
+                    .chunks(raw_page_size)
+                    .flat_map(|p| &p[..VMC_PAGE_SIZE])
+                    .copied()
+                    .collect();
+                (data, true)
+            }
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+
+        let superblock = Superblock::parse(&data)?;
+        let cluster_size = superblock.page_len as usize * superblock.pages_per_cluster as usize;
+        let clusters = data.len() / VMC_CLUSTER_SIZE;
+        if cluster_size != VMC_CLUSTER_SIZE
+            || superblock.alloc_offset as usize + superblock.alloc_end as usize > clusters
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let card = VmcImage {
+            data,
+            superblock,
+            ecc,
+        };
+
+        // Every FAT cluster reachable from superblock has to be on the card,
+        // so later lookups cannot run past its end.
+        let epc = VmcImage::entries_per_cluster();
+        let fat_clusters = card.superblock.alloc_end.div_ceil(epc);
+        let in_card = |cluster: u32| (cluster as usize) < clusters;
+        let fat_valid =
+            (0..fat_clusters).all(|i| match card.superblock.ifc_list.get((i / epc) as usize) {
+                Some(ifc) if in_card(*ifc) => in_card(card.read_u32(*ifc, i % epc)),
+                _ => false,
+            });
+
+        if card.superblock.rootdir_cluster >= card.superblock.alloc_end || !fat_valid {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Ok(card)
+    }
+
+    fn entries_per_cluster() -> u32 {
+        (VMC_CLUSTER_SIZE / FAT_ENTRY_SIZE) as u32
+    }
+
+    fn cluster(&self, cluster: u32) -> &[u8] {
+        let offset = cluster as usize * VMC_CLUSTER_SIZE;
+        &self.data[offset..offset + VMC_CLUSTER_SIZE]
+    }
+
+    fn cluster_mut(&mut self, cluster: u32) -> &mut [u8] {
+        let offset = cluster as usize * VMC_CLUSTER_SIZE;
+        &mut self.data[offset..offset + VMC_CLUSTER_SIZE]
+    }
+
+    fn read_u32(&self, cluster: u32, index: u32) -> u32 {
+        let offset = index as usize * FAT_ENTRY_SIZE;
+        let mut bytes = [0x00; FAT_ENTRY_SIZE];
+        bytes.copy_from_slice(&self.cluster(cluster)[offset..offset + FAT_ENTRY_SIZE]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn write_u32(&mut self, cluster: u32, index: u32, value: u32) {
+        let offset = index as usize * FAT_ENTRY_SIZE;
+        self.cluster_mut(cluster)[offset..offset + FAT_ENTRY_SIZE]
+            .copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Fills indirect FAT clusters and marks all allocatable clusters free.
+    fn write_fat_tables(&mut self) {
+        let epc = VmcImage::entries_per_cluster();
+        let first_fat = self.superblock.ifc_list.last().unwrap() + 1;
+        let fat_clusters = self.superblock.alloc_offset - first_fat;
+
+        for i in 0..fat_clusters {
+            let ifc = self.superblock.ifc_list[(i / epc) as usize];
+            self.write_u32(ifc, i % epc, first_fat + i);
+        }
+
+        for cluster in 0..self.superblock.alloc_end {
+            self.set_fat(cluster, FAT_FREE);
+        }
+    }
+
+    /// FAT cluster and index in it holding entry of allocatable `cluster`.
+    fn fat_position(&self, cluster: u32) -> (u32, u32) {
+        let epc = VmcImage::entries_per_cluster();
+        let fat_index = cluster / epc;
+        let ifc = self.superblock.ifc_list[(fat_index / epc) as usize];
+        (self.read_u32(ifc, fat_index % epc), cluster % epc)
+    }
+
+    fn get_fat(&self, cluster: u32) -> u32 {
+        let (fat_cluster, index) = self.fat_position(cluster);
+        self.read_u32(fat_cluster, index)
+    }
+
+    /// Sets FAT entry of allocatable `cluster`.
+    fn set_fat(&mut self, cluster: u32, value: u32) {
+        // Allocated entries carry the flag on top of next cluster in chain.
+        let value = match value {
+            FAT_FREE | FAT_CHAIN_END => value,
+            v => v | FAT_ALLOCATED,
+        };
+
+        let (fat_cluster, index) = self.fat_position(cluster);
+        self.write_u32(fat_cluster, index, value);
+    }
+
+    /// Allocatable clusters linked in chain starting at `start`.
+    fn chain(&self, start: u32) -> Result<Vec<u32>> {
+        let mut clusters = Vec::new();
+        let mut cluster = start;
+
+        loop {
+            if cluster >= self.superblock.alloc_end
+                || clusters.len() as u32 >= self.superblock.alloc_end
+            {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+
+            clusters.push(cluster);
+            match self.get_fat(cluster) {
+                FAT_CHAIN_END => return Ok(clusters),
+                next if next & FAT_ALLOCATED != 0 => cluster = next & !FAT_ALLOCATED,
+                _ => return Err(Error::from(ErrorKind::InvalidData)),
+            }
+        }
+    }
+
+    fn free_clusters(&self) -> Vec<u32> {
+        (0..self.superblock.alloc_end)
+            .filter(|c| self.get_fat(*c) == FAT_FREE)
+            .collect()
+    }
+
+    pub fn free_space(&self) -> u64 {
+        (self.free_clusters().len() * VMC_CLUSTER_SIZE) as u64
+    }
+
+    /// Links `count` free clusters into new chain.
+    fn allocate(&mut self, count: usize) -> Result<Vec<u32>> {
+        let clusters = self.free_clusters();
+        if clusters.len() < count {
+            return Err(Error::from(ErrorKind::OutOfMemory));
+        }
+
+        let clusters = clusters[..count].to_vec();
+        for pair in clusters.windows(2) {
+            self.set_fat(pair[0], pair[1]);
+        }
+
+        if let Some(last) = clusters.last() {
+            self.set_fat(*last, FAT_CHAIN_END);
+        }
+
+        Ok(clusters)
+    }
+
+    fn read_entry(&self, chain: &[u32], index: usize) -> Result<DirEntry> {
+        let cluster = chain
+            .get(index / VMC_ENTRIES_PER_CLUSTER)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+        let offset = (index % VMC_ENTRIES_PER_CLUSTER) * ENTRY_SIZE;
+        let cluster = self.cluster(self.superblock.alloc_offset + cluster);
+        DirEntry::parse(&cluster[offset..offset + ENTRY_SIZE])
+    }
+
+    fn write_entry(&mut self, chain: &[u32], index: usize, entry: &DirEntry) {
+        let cluster = self.superblock.alloc_offset + chain[index / VMC_ENTRIES_PER_CLUSTER];
+        let offset = (index % VMC_ENTRIES_PER_CLUSTER) * ENTRY_SIZE;
+        self.cluster_mut(cluster)[offset..offset + ENTRY_SIZE].copy_from_slice(&entry.to_bytes());
+    }
+
+    /// Entries of directory described by its `.` entry, including `.` and `..`.
+    fn read_dir(&self, dir: &DirEntry) -> Result<Vec<DirEntry>> {
+        let chain = self.chain(dir.cluster)?;
+        (0..dir.length as usize)
+            .map(|i| self.read_entry(&chain, i))
+            .collect()
+    }
+
+    fn root(&self) -> Result<DirEntry> {
+        self.read_entry(&[self.superblock.rootdir_cluster], 0)
+    }
+
+    fn read_file(&self, entry: &DirEntry) -> Result<Vec<u8>> {
+        if entry.length == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::with_capacity(entry.length as usize);
+        for cluster in self.chain(entry.cluster)? {
+            data.extend_from_slice(self.cluster(self.superblock.alloc_offset + cluster));
+        }
+
+        if data.len() < entry.length as usize {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        data.truncate(entry.length as usize);
+        Ok(data)
+    }
+
+    /// Save folders in root directory, along with their files.
+    pub fn saves(&self) -> Result<Vec<SaveFolder>> {
+        let mut saves = Vec::new();
+
+        for entry in self.read_dir(&self.root()?)?.into_iter().skip(2) {
+            if !entry.exists() || !entry.is_dir() {
+                continue;
+            }
+
+            let mut files = Vec::new();
+            for file in self.read_dir(&entry)?.into_iter().skip(2) {
+                if !file.exists() || file.is_dir() {
+                    continue;
+                }
+
+                let data = self.read_file(&file)?;
+                files.push(SaveFile { entry: file, data });
+            }
+
+            saves.push(SaveFolder { entry, files });
+        }
+
+        Ok(saves)
+    }
+
+    /// Copies `save` folder into root directory.
+    pub fn add_save(&mut self, save: &SaveFolder) -> Result<()> {
+        if self
+            .saves()?
+            .iter()
+            .any(|s| s.entry.name == save.entry.name)
+        {
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+
+        let mut root = self.root()?;
+        let mut root_chain = self.chain(root.cluster)?;
+        let index = root.length as usize;
+        let dir_length = save.files.len() + 2;
+        let file_clusters = save
+            .files
+            .iter()
+            .map(|f| f.data.len().div_ceil(VMC_CLUSTER_SIZE))
+            .collect::<Vec<usize>>();
+
+        let root_grows = index >= root_chain.len() * VMC_ENTRIES_PER_CLUSTER;
+        let needed = dir_length.div_ceil(VMC_ENTRIES_PER_CLUSTER)
+            + file_clusters.iter().sum::<usize>()
+            + root_grows as usize;
+        if self.free_clusters().len() < needed {
+            return Err(Error::from(ErrorKind::OutOfMemory));
+        }
+
+        if root_grows {
+            let cluster = self.allocate(1)?[0];
+            self.set_fat(*root_chain.last().unwrap(), cluster);
+            root_chain.push(cluster);
+        }
+
+        let dir_chain = self.allocate(dir_length.div_ceil(VMC_ENTRIES_PER_CLUSTER))?;
+        let mut dir = save.entry.clone();
+        dir.mode = DIR_MODE;
+        dir.length = dir_length as u32;
+        dir.cluster = dir_chain[0];
+        self.write_entry(&root_chain, index, &dir);
+
+        let mut this = DirEntry::new(".", DIR_MODE, 0, root.cluster);
+        this.dir_entry = index as u32;
+        let parent = DirEntry::new("..", PARENT_MODE, 0, 0);
+        self.write_entry(&dir_chain, 0, &this);
+        self.write_entry(&dir_chain, 1, &parent);
+
+        for (i, (file, count)) in save.files.iter().zip(file_clusters).enumerate() {
+            let chain = self.allocate(count)?;
+            for (cluster, data) in chain.iter().zip(file.data.chunks(VMC_CLUSTER_SIZE)) {
+                let cluster = self.cluster_mut(self.superblock.alloc_offset + cluster);
+                cluster[..data.len()].copy_from_slice(data);
+            }
+
+            let mut entry = file.entry.clone();
+            entry.length = file.data.len() as u32;
+            entry.cluster = chain.first().copied().unwrap_or(FAT_CHAIN_END);
+            self.write_entry(&dir_chain, i + 2, &entry);
+        }
+
+        root.length += 1;
+        self.write_entry(&root_chain, 0, &root);
+        Ok(())
+    }
+
+    /// Writes card to `path`, in the same ECC layout it was opened or created with.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        for page in self.data.chunks(VMC_PAGE_SIZE) {
+            writer.write_all(page)?;
+
+            if self.ecc {
+                let mut spare = [0x00; VMC_SPARE_SIZE];
+                for (i, chunk) in page.chunks(ECC_CHUNK_SIZE).enumerate() {
+                    spare[i * 3..i * 3 + 3].copy_from_slice(&ecc::calculate(chunk));
+                }
+                writer.write_all(&spare)?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use crate::vmc::entry::FILE_MODE;
+    use crate::vmc::superblock::SUPERBLOCK_MAGIC;
+    use std::fs::metadata;
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    fn make_save(name: &str, sizes: &[usize]) -> SaveFolder {
+        let files = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| SaveFile {
+                entry: DirEntry::new(&format!("file{}", i), FILE_MODE, *size as u32, 0),
+                data: (0..*size).map(|b| (b % 253) as u8).collect(),
+            })
+            .collect();
+
+        SaveFolder {
+            entry: DirEntry::new(name, DIR_MODE, 0, 0),
+            files,
+        }
+    }
+
+    #[test]
+    fn test_format_invalid_size() {
+        assert!(VmcImage::format(4, false).is_err());
+    }
+
+    #[test]
+    fn test_format_8mb() {
+        let card = VmcImage::format(8, false).unwrap();
+        assert_eq!(card.data.len(), 8 * 1024 * 1024);
+        assert_eq!(&card.data[..28], SUPERBLOCK_MAGIC);
+
+        // Indirect FAT cluster 8 points at FAT clusters 9 and onwards.
+        assert_eq!(read_u32(&card.data, 8 * VMC_CLUSTER_SIZE), 9);
+        assert_eq!(read_u32(&card.data, 9 * VMC_CLUSTER_SIZE), FAT_CHAIN_END);
+        assert_eq!(read_u32(&card.data, 9 * VMC_CLUSTER_SIZE + 4), FAT_FREE);
+
+        // Root directory in first allocatable cluster holds `.` and `..`.
+        let root = 41 * VMC_CLUSTER_SIZE;
+        assert_eq!(read_u32(&card.data, root + 4), 2);
+        assert_eq!(card.data[root + 0x40], b'.');
+        assert_eq!(
+            &card.data[root + ENTRY_SIZE + 0x40..root + ENTRY_SIZE + 0x42],
+            b".."
+        );
+        assert!(card.saves().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_saves() {
+        let mut card = VmcImage::format(8, false).unwrap();
+        let free = card.free_space();
+
+        // Root directory grows by a cluster with every other save.
+        card.add_save(&make_save("BESLES-11111", &[964, 3000]))
+            .unwrap();
+        card.add_save(&make_save("BESLES-22222", &[0])).unwrap();
+        card.add_save(&make_save("BESLES-33333", &[2048])).unwrap();
+        assert!(card.add_save(&make_save("BESLES-22222", &[1])).is_err());
+
+        let saves = card.saves().unwrap();
+        let names = saves
+            .iter()
+            .map(|s| s.entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["BESLES-11111", "BESLES-22222", "BESLES-33333"]);
+        assert!(saves[0].files[1].data == make_save("", &[964, 3000]).files[1].data);
+        assert_eq!(saves[1].files[0].data.len(), 0);
+
+        // Clusters: 4 + 0 + 2 data, 2 + 2 + 2 directories, 2 for root.
+        assert_eq!(free - card.free_space(), 14 * VMC_CLUSTER_SIZE as u64);
+    }
+
+    #[test]
+    fn test_add_save_too_big() {
+        let mut card = VmcImage::format(8, false).unwrap();
+        let save = make_save("BESLES-11111", &[9 * 1024 * 1024]);
+        assert_eq!(
+            card.add_save(&save).unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+    }
+
+    #[test]
+    fn test_save_and_open_with_ecc() {
+        let dir = TestDir::new("vmc_ecc");
+        let path = dir.join("ecc.bin");
+        let mut card = VmcImage::format(8, true).unwrap();
+        card.add_save(&make_save("BESLES-11111", &[100])).unwrap();
+        card.save(&path).unwrap();
+
+        let size = metadata(&path).unwrap().len();
+        assert_eq!(size, 16384 * (VMC_PAGE_SIZE + VMC_SPARE_SIZE) as u64);
+
+        let card = VmcImage::open(&path).unwrap();
+        assert!(card.ecc);
+        assert_eq!(card.saves().unwrap()[0].files[0].data.len(), 100);
+    }
+
+    #[test]
+    fn test_open_corrupt_superblock() {
+        let dir = TestDir::new("vmc_corrupt");
+        let path = dir.join("corrupt.bin");
+        let card = VmcImage::format(8, false).unwrap();
+        let open_with = |offset: usize, value: u32| {
+            let mut data = card.data.clone();
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            std::fs::write(&path, data).unwrap();
+            VmcImage::open(&path)
+        };
+
+        // Indirect FAT cluster, FAT cluster it points at and root directory.
+        assert!(open_with(0x50, 8).is_ok());
+        assert!(open_with(0x50, 0x0100_0000).is_err());
+        assert!(open_with(8 * VMC_CLUSTER_SIZE, 0xffff_ffff).is_err());
+        assert!(open_with(0x3c, 8135).is_err());
+    }
+}
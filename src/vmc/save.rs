@@ -0,0 +1,431 @@
+use crate::vmc::entry::{DirEntry, DIR_MODE, ENTRY_SIZE, FILE_MODE, MODE_EXISTS, PARENT_MODE};
+use crate::vmc::lzari;
+
+use std::fs::{read, write};
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use flate2::Crc;
+
+// EMS/uLaunchELF .psu keeps file data padded to whole clusters.
+const PSU_DATA_ALIGN: usize = 1024;
+const MAX_MAGIC: &[u8; 12] = b"Ps2PowerSave";
+const MAX_HEADER_SIZE: usize = 0x5c;
+const MAX_NAME_SIZE: usize = 32;
+const MAX_CRC: usize = 0x0c;
+const MAX_DIRNAME: usize = 0x10;
+const MAX_ICONSYS: usize = 0x30;
+const MAX_COMPRESSED_SIZE: usize = 0x50;
+const MAX_NUM_FILES: usize = 0x54;
+const MAX_SIZE: usize = 0x58;
+// No save holds more than the largest memory card.
+const MAX_DATA_LIMIT: usize = 64 * 1024 * 1024;
+const CBS_MAGIC: &[u8; 4] = b"CFU\0";
+const CBS_HEADER_LENGTH: usize = 0x08;
+const CBS_DATA_SIZE: usize = 0x0c;
+const CBS_DIRNAME: usize = 0x14;
+const CBS_CREATED: usize = 0x34;
+const CBS_MODIFIED: usize = 0x3c;
+const CBS_MIN_HEADER_SIZE: usize = 0x5c;
+const CBS_FILE_HEADER_SIZE: usize = 0x40;
+const CBS_FILE_SIZE: usize = 0x10;
+const CBS_FILE_MODE: usize = 0x14;
+const CBS_FILE_NAME: usize = 0x20;
+// CodeBreaker encrypts every save with RC4 started from this fixed state.
+const CBS_RC4_STATE: [u8; 256] = [
+    0x5f, 0x1f, 0x85, 0x6f, 0x31, 0xaa, 0x3b, 0x18, 0x21, 0xb9, 0xce, 0x1c, 0x07, 0x4c, 0x9c, 0xb4,
+    0x81, 0xb8, 0xef, 0x98, 0x59, 0xae, 0xf9, 0x26, 0xe3, 0x80, 0xa3, 0x29, 0x2d, 0x73, 0x51, 0x62,
+    0x7c, 0x64, 0x46, 0xf4, 0x34, 0x1a, 0xf6, 0xe1, 0xba, 0x3a, 0x0d, 0x82, 0x79, 0x0a, 0x5c, 0x16,
+    0x71, 0x49, 0x8e, 0xac, 0x8c, 0x9f, 0x35, 0x19, 0x45, 0x94, 0x3f, 0x56, 0x0c, 0x91, 0x00, 0x0b,
+    0xd7, 0xb0, 0xdd, 0x39, 0x66, 0xa1, 0x76, 0x52, 0x13, 0x57, 0xf3, 0xbb, 0x4e, 0xe5, 0xdc, 0xf0,
+    0x65, 0x84, 0xb2, 0xd6, 0xdf, 0x15, 0x3c, 0x63, 0x1d, 0x89, 0x14, 0xbd, 0xd2, 0x36, 0xfe, 0xb1,
+    0xca, 0x8b, 0xa4, 0xc6, 0x9e, 0x67, 0x47, 0x37, 0x42, 0x6d, 0x6a, 0x03, 0x92, 0x70, 0x05, 0x7d,
+    0x96, 0x2f, 0x40, 0x90, 0xc4, 0xf1, 0x3e, 0x3d, 0x01, 0xf7, 0x68, 0x1e, 0xc3, 0xfc, 0x72, 0xb5,
+    0x54, 0xcf, 0xe7, 0x41, 0xe4, 0x4d, 0x83, 0x55, 0x12, 0x22, 0x09, 0x78, 0xfa, 0xde, 0xa7, 0x06,
+    0x08, 0x23, 0xbf, 0x0f, 0xcc, 0xc1, 0x97, 0x61, 0xc5, 0x4a, 0xe6, 0xa0, 0x11, 0xc2, 0xea, 0x74,
+    0x02, 0x87, 0xd5, 0xd1, 0x9d, 0xb7, 0x7e, 0x38, 0x60, 0x53, 0x95, 0x8d, 0x25, 0x77, 0x10, 0x5e,
+    0x9b, 0x7f, 0xd8, 0x6e, 0xda, 0xa2, 0x2e, 0x20, 0x4f, 0xcd, 0x8f, 0xcb, 0xbe, 0x5a, 0xe0, 0xed,
+    0x2c, 0x9a, 0xd4, 0xe2, 0xaf, 0xd0, 0xa9, 0xe8, 0xad, 0x7a, 0xbc, 0xa8, 0xf2, 0xee, 0xeb, 0xf5,
+    0xa6, 0x99, 0x28, 0x24, 0x6c, 0x2b, 0x75, 0x5d, 0xf8, 0xd3, 0x86, 0x17, 0xfb, 0xc0, 0x7b, 0xb3,
+    0x58, 0xdb, 0xc7, 0x4b, 0xff, 0x04, 0x50, 0xe9, 0x88, 0x69, 0xc9, 0x2a, 0xab, 0xfd, 0x5b, 0x1b,
+    0x8a, 0xd9, 0xec, 0x27, 0x44, 0x0e, 0x33, 0xc8, 0x6b, 0x93, 0x32, 0x48, 0xb6, 0x30, 0x43, 0xa5,
+];
+
+pub struct SaveFile {
+    pub entry: DirEntry,
+    pub data: Vec<u8>,
+}
+
+/// Save folder with its files, as stored in the root of a memory card.
+pub struct SaveFolder {
+    pub entry: DirEntry,
+    pub files: Vec<SaveFile>,
+}
+
+fn get_u32(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0x00; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn get_str(buffer: &[u8], offset: usize, size: usize) -> String {
+    let raw = &buffer[offset..offset + size];
+    let end = raw.iter().position(|b| *b == 0x00).unwrap_or(size);
+    String::from_utf8_lossy(&raw[..end]).to_string()
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn get_tod(buffer: &[u8], offset: usize) -> [u8; 8] {
+    let mut tod = [0x00; 8];
+    tod.copy_from_slice(&buffer[offset..offset + 8]);
+    tod
+}
+
+/// Encrypts or decrypts `data` in place, as RC4 is its own inverse.
+fn cbs_crypt(data: &mut [u8]) {
+    let mut state = CBS_RC4_STATE;
+    let (mut i, mut j) = (0u8, 0u8);
+
+    for byte in data.iter_mut() {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(state[i as usize]);
+        state.swap(i as usize, j as usize);
+        let k = state[i as usize].wrapping_add(state[j as usize]);
+        *byte ^= state[k as usize];
+    }
+}
+
+impl SaveFolder {
+    /// Loads `.psu`, `.max` or `.cbs` save, told apart by their contents.
+    pub fn load(path: &Path) -> Result<Self> {
+        let buffer = read(path)?;
+
+        if buffer.starts_with(MAX_MAGIC) {
+            return SaveFolder::parse_max(&buffer);
+        }
+
+        if buffer.starts_with(CBS_MAGIC) {
+            return SaveFolder::parse_cbs(&buffer);
+        }
+
+        SaveFolder::parse_psu(&buffer)
+    }
+
+    /// Writes save as `.max` if `path` has such extension, `.psu` otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let is_max = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("max"));
+
+        match is_max {
+            true => write(path, self.to_max()),
+            false => write(path, self.to_psu()),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.files.iter().map(|f| f.data.len()).sum()
+    }
+
+    fn parse_psu(buffer: &[u8]) -> Result<Self> {
+        let entry = DirEntry::parse(buffer)?;
+        if !entry.is_dir() || entry.length < 2 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // Entries for `.` and `..` follow the folder itself.
+        let mut offset = ENTRY_SIZE * 3;
+        let mut files = Vec::new();
+
+        for _ in 2..entry.length {
+            let file = DirEntry::parse(buffer.get(offset..).unwrap_or_default())?;
+            offset += ENTRY_SIZE;
+
+            let length = file.length as usize;
+            let data = buffer
+                .get(offset..offset + length)
+                .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?
+                .to_vec();
+            offset += align(length, PSU_DATA_ALIGN);
+            files.push(SaveFile { entry: file, data });
+        }
+
+        Ok(SaveFolder { entry, files })
+    }
+
+    fn to_psu(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut entry = self.entry.clone();
+        entry.mode = DIR_MODE;
+        entry.length = self.files.len() as u32 + 2;
+        buffer.extend_from_slice(&entry.to_bytes());
+
+        for (name, mode) in [(".", DIR_MODE), ("..", PARENT_MODE)] {
+            let mut link = DirEntry::new(name, mode, 0, 0);
+            link.created = self.entry.created;
+            link.modified = self.entry.modified;
+            buffer.extend_from_slice(&link.to_bytes());
+        }
+
+        for file in &self.files {
+            buffer.extend_from_slice(&file.entry.to_bytes());
+            let mut data = file.data.clone();
+            data.resize(align(data.len(), PSU_DATA_ALIGN), 0xff);
+            buffer.extend_from_slice(&data);
+        }
+
+        buffer
+    }
+
+    fn parse_max(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < MAX_HEADER_SIZE {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let name = get_str(buffer, MAX_DIRNAME, MAX_NAME_SIZE);
+        let num_files = get_u32(buffer, MAX_NUM_FILES) as usize;
+        let size = get_u32(buffer, MAX_SIZE) as usize;
+        if size > MAX_DATA_LIMIT {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        // Compressed size counts the uncompressed size field too.
+        let compressed_size = (get_u32(buffer, MAX_COMPRESSED_SIZE) as usize).saturating_sub(4);
+        let end = (MAX_HEADER_SIZE + compressed_size).min(buffer.len());
+        let data = lzari::decode(&buffer[MAX_HEADER_SIZE..end], size)?;
+
+        let mut offset = 0;
+        let mut files = Vec::new();
+        for _ in 0..num_files {
+            let header = data
+                .get(offset..offset + 4 + MAX_NAME_SIZE)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+            let length = get_u32(header, 0) as usize;
+            let name = get_str(header, 4, MAX_NAME_SIZE);
+            offset += 4 + MAX_NAME_SIZE;
+
+            let contents = data
+                .get(offset..offset + length)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidData))?
+                .to_vec();
+            // Each file header starts 8 bytes before 16 byte boundary.
+            offset = align(offset + length + 8, 16) - 8;
+
+            let entry = DirEntry::new(&name, FILE_MODE, length as u32, 0);
+            files.push(SaveFile {
+                entry,
+                data: contents,
+            });
+        }
+
+        let entry = DirEntry::new(&name, DIR_MODE, files.len() as u32 + 2, 0);
+        Ok(SaveFolder { entry, files })
+    }
+
+    fn parse_cbs(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < CBS_MIN_HEADER_SIZE {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let header_length = get_u32(buffer, CBS_HEADER_LENGTH) as usize;
+        let size = get_u32(buffer, CBS_DATA_SIZE) as usize;
+        if header_length < CBS_MIN_HEADER_SIZE || size > MAX_DATA_LIMIT {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let mut compressed = buffer
+            .get(header_length..)
+            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?
+            .to_vec();
+        cbs_crypt(&mut compressed);
+
+        let mut data = Vec::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .take(size as u64)
+            .read_to_end(&mut data)?;
+
+        let mut offset = 0;
+        let mut files = Vec::new();
+        while offset < data.len() {
+            let header = data
+                .get(offset..offset + CBS_FILE_HEADER_SIZE)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+            let length = get_u32(header, CBS_FILE_SIZE) as usize;
+            let name = get_str(header, CBS_FILE_NAME, MAX_NAME_SIZE);
+            let mode = u16::from_le_bytes([header[CBS_FILE_MODE], header[CBS_FILE_MODE + 1]]);
+
+            let mut entry = DirEntry::new(&name, FILE_MODE, length as u32, 0);
+            if mode & MODE_EXISTS != 0 {
+                entry.mode = mode;
+            }
+            entry.created = get_tod(header, 0);
+            entry.modified = get_tod(header, 8);
+            offset += CBS_FILE_HEADER_SIZE;
+
+            let contents = data
+                .get(offset..offset + length)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidData))?
+                .to_vec();
+            offset += length;
+            files.push(SaveFile {
+                entry,
+                data: contents,
+            });
+        }
+
+        let name = get_str(buffer, CBS_DIRNAME, MAX_NAME_SIZE);
+        let mut entry = DirEntry::new(&name, DIR_MODE, files.len() as u32 + 2, 0);
+        entry.created = get_tod(buffer, CBS_CREATED);
+        entry.modified = get_tod(buffer, CBS_MODIFIED);
+        Ok(SaveFolder { entry, files })
+    }
+
+    fn to_max(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for file in &self.files {
+            let mut name = [0x00; MAX_NAME_SIZE];
+            let len = file.entry.name.len().min(MAX_NAME_SIZE);
+            name[..len].copy_from_slice(&file.entry.name.as_bytes()[..len]);
+
+            data.extend_from_slice(&(file.data.len() as u32).to_le_bytes());
+            data.extend_from_slice(&name);
+            data.extend_from_slice(&file.data);
+            data.resize(align(data.len() + 8, 16) - 8, 0x00);
+        }
+
+        let compressed = lzari::encode(&data);
+        let mut buffer = vec![0x00; MAX_HEADER_SIZE];
+        let name = &self.entry.name.as_bytes()[..self.entry.name.len().min(MAX_NAME_SIZE)];
+        buffer[..MAX_MAGIC.len()].copy_from_slice(MAX_MAGIC);
+        buffer[MAX_DIRNAME..MAX_DIRNAME + name.len()].copy_from_slice(name);
+        buffer[MAX_ICONSYS..MAX_ICONSYS + name.len()].copy_from_slice(name);
+
+        let fields = [
+            (MAX_COMPRESSED_SIZE, compressed.len() as u32 + 4),
+            (MAX_NUM_FILES, self.files.len() as u32),
+            (MAX_SIZE, data.len() as u32),
+        ];
+
+        for (offset, value) in fields {
+            buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        buffer.extend_from_slice(&compressed);
+
+        // CRC covers the whole file, with CRC field itself zeroed.
+        let mut crc = Crc::new();
+        crc.update(&buffer);
+        buffer[MAX_CRC..MAX_CRC + 4].copy_from_slice(&crc.sum().to_le_bytes());
+
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    fn make_save() -> SaveFolder {
+        let files = [
+            ("icon.sys", vec![0x50; 964]),
+            ("BESLES-12345", vec![0x01; 3000]),
+        ]
+        .into_iter()
+        .map(|(name, data)| SaveFile {
+            entry: DirEntry::new(name, FILE_MODE, data.len() as u32, 0),
+            data,
+        })
+        .collect();
+
+        SaveFolder {
+            entry: DirEntry::new("BESLES-12345", DIR_MODE, 4, 0),
+            files,
+        }
+    }
+
+    fn assert_same(a: &SaveFolder, b: &SaveFolder) {
+        assert_eq!(a.entry.name, b.entry.name);
+        assert_eq!(a.files.len(), b.files.len());
+        for (x, y) in a.files.iter().zip(b.files.iter()) {
+            assert_eq!(x.entry.name, y.entry.name);
+            assert!(x.data == y.data);
+        }
+    }
+
+    #[test]
+    fn test_psu_roundtrip() {
+        let save = make_save();
+        let psu = save.to_psu();
+        assert_eq!(psu.len(), 3 * 512 + 512 + 1024 + 512 + 3072);
+        assert_same(&save, &SaveFolder::parse_psu(&psu).unwrap());
+    }
+
+    #[test]
+    fn test_max_roundtrip() {
+        let save = make_save();
+        let max = save.to_max();
+        assert!(max.starts_with(MAX_MAGIC));
+        assert_same(&save, &SaveFolder::parse_max(&max).unwrap());
+    }
+
+    fn make_cbs(save: &SaveFolder) -> Vec<u8> {
+        let mut data = Vec::new();
+        for file in &save.files {
+            let mut header = [0x00; CBS_FILE_HEADER_SIZE];
+            header[CBS_FILE_SIZE..CBS_FILE_SIZE + 4]
+                .copy_from_slice(&(file.data.len() as u32).to_le_bytes());
+            header[CBS_FILE_MODE..CBS_FILE_MODE + 2].copy_from_slice(&FILE_MODE.to_le_bytes());
+            let name = file.entry.name.as_bytes();
+            header[CBS_FILE_NAME..CBS_FILE_NAME + name.len()].copy_from_slice(name);
+            data.extend_from_slice(&header);
+            data.extend_from_slice(&file.data);
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        cbs_crypt(&mut compressed);
+
+        let mut buffer = vec![0x00; 0x128];
+        buffer[..4].copy_from_slice(CBS_MAGIC);
+        buffer[CBS_HEADER_LENGTH..CBS_HEADER_LENGTH + 4].copy_from_slice(&0x128u32.to_le_bytes());
+        buffer[CBS_DATA_SIZE..CBS_DATA_SIZE + 4]
+            .copy_from_slice(&(data.len() as u32).to_le_bytes());
+        let name = save.entry.name.as_bytes();
+        buffer[CBS_DIRNAME..CBS_DIRNAME + name.len()].copy_from_slice(name);
+        buffer.extend_from_slice(&compressed);
+        buffer
+    }
+
+    #[test]
+    fn test_parse_cbs() {
+        let save = make_save();
+        let cbs = make_cbs(&save);
+        assert_same(&save, &SaveFolder::parse_cbs(&cbs).unwrap());
+
+        // Payload left unencrypted does not inflate.
+        let mut plain = cbs.clone();
+        cbs_crypt(&mut plain[0x128..]);
+        assert!(SaveFolder::parse_cbs(&plain).is_err());
+    }
+
+    #[test]
+    fn test_max_corrupt_size() {
+        let mut max = make_save().to_max();
+        max[MAX_SIZE..MAX_SIZE + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = SaveFolder::parse_max(&max).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        // Size within limit, but larger than compressed data holds.
+        max[MAX_SIZE..MAX_SIZE + 4].copy_from_slice(&(MAX_DATA_LIMIT as u32).to_le_bytes());
+        let error = SaveFolder::parse_max(&max).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
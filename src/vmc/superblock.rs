@@ -0,0 +1,180 @@
+use std::io::{Error, ErrorKind, Result};
+
+pub const SUPERBLOCK_MAGIC: &[u8; 28] = b"Sony PS2 Memory Card Format ";
+const SUPERBLOCK_VERSION: &[u8; 7] = b"1.2.0.0";
+const SUPERBLOCK_MAX_IFC: usize = 32;
+const SUPERBLOCK_MAX_BAD_BLOCKS: usize = 32;
+const CARD_TYPE_PS2: u8 = 2;
+const CARD_FLAGS: u8 = 0x52;
+
+const SB_VERSION: usize = 0x01c;
+const SB_PAGE_LEN: usize = 0x028;
+const SB_PAGES_PER_CLUSTER: usize = 0x02a;
+const SB_PAGES_PER_BLOCK: usize = 0x02c;
+const SB_UNKNOWN: usize = 0x02e;
+const SB_CLUSTERS_PER_CARD: usize = 0x030;
+const SB_ALLOC_OFFSET: usize = 0x034;
+const SB_ALLOC_END: usize = 0x038;
+const SB_ROOTDIR_CLUSTER: usize = 0x03c;
+const SB_BACKUP_BLOCK1: usize = 0x040;
+const SB_BACKUP_BLOCK2: usize = 0x044;
+const SB_IFC_LIST: usize = 0x050;
+const SB_BAD_BLOCK_LIST: usize = 0x0d0;
+const SB_CARD_TYPE: usize = 0x150;
+const SB_CARD_FLAGS: usize = 0x151;
+pub const SUPERBLOCK_SIZE: usize = 0x154;
+
+/// Memory card superblock, stored in the very first page.
+pub struct Superblock {
+    pub page_len: u16,
+    pub pages_per_cluster: u16,
+    pub pages_per_block: u16,
+    pub clusters_per_card: u32,
+    pub alloc_offset: u32,
+    pub alloc_end: u32,
+    pub rootdir_cluster: u32,
+    pub backup_block1: u32,
+    pub backup_block2: u32,
+    pub ifc_list: Vec<u32>,
+}
+
+impl Superblock {
+    /// Layout of freshly formatted card with `clusters_per_card` clusters.
+    ///
+    /// First erase block holds superblock, followed by indirect FAT
+    /// clusters and FAT itself. Last two blocks are kept for backup.
+    pub fn new(
+        page_len: u16,
+        pages_per_cluster: u16,
+        pages_per_block: u16,
+        clusters_per_card: u32,
+    ) -> Self {
+        let cluster_size = page_len as u32 * pages_per_cluster as u32;
+        let entries_per_cluster = cluster_size / 4;
+        let clusters_per_block = (pages_per_block / pages_per_cluster) as u32;
+        let blocks = clusters_per_card / clusters_per_block;
+
+        let fat_clusters = clusters_per_card.div_ceil(entries_per_cluster);
+        let ifc_clusters = fat_clusters.div_ceil(entries_per_cluster);
+        let first_ifc = clusters_per_block;
+        let alloc_offset = first_ifc + ifc_clusters + fat_clusters;
+
+        Superblock {
+            page_len,
+            pages_per_cluster,
+            pages_per_block,
+            clusters_per_card,
+            alloc_offset,
+            alloc_end: (blocks - 2) * clusters_per_block - alloc_offset,
+            rootdir_cluster: 0,
+            backup_block1: blocks - 1,
+            backup_block2: blocks - 2,
+            ifc_list: (first_ifc..first_ifc + ifc_clusters).collect(),
+        }
+    }
+
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < SUPERBLOCK_SIZE || !buffer.starts_with(SUPERBLOCK_MAGIC) {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let get_u16 = |offset: usize| u16::from_le_bytes([buffer[offset], buffer[offset + 1]]);
+        let get_u32 = |offset: usize| {
+            let mut bytes = [0x00; 4];
+            bytes.copy_from_slice(&buffer[offset..offset + 4]);
+            u32::from_le_bytes(bytes)
+        };
+
+        // Unused slots are zeroed, cluster 0 always holds the superblock.
+        let ifc_list = (0..SUPERBLOCK_MAX_IFC)
+            .map(|i| get_u32(SB_IFC_LIST + i * 4))
+            .take_while(|c| *c != 0)
+            .collect::<Vec<u32>>();
+
+        if ifc_list.is_empty() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Ok(Superblock {
+            page_len: get_u16(SB_PAGE_LEN),
+            pages_per_cluster: get_u16(SB_PAGES_PER_CLUSTER),
+            pages_per_block: get_u16(SB_PAGES_PER_BLOCK),
+            clusters_per_card: get_u32(SB_CLUSTERS_PER_CARD),
+            alloc_offset: get_u32(SB_ALLOC_OFFSET),
+            alloc_end: get_u32(SB_ALLOC_END),
+            rootdir_cluster: get_u32(SB_ROOTDIR_CLUSTER),
+            backup_block1: get_u32(SB_BACKUP_BLOCK1),
+            backup_block2: get_u32(SB_BACKUP_BLOCK2),
+            ifc_list,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = vec![0x00; SUPERBLOCK_SIZE];
+        raw[..SUPERBLOCK_MAGIC.len()].copy_from_slice(SUPERBLOCK_MAGIC);
+        raw[SB_VERSION..SB_VERSION + SUPERBLOCK_VERSION.len()].copy_from_slice(SUPERBLOCK_VERSION);
+
+        let fields16 = [
+            (SB_PAGE_LEN, self.page_len),
+            (SB_PAGES_PER_CLUSTER, self.pages_per_cluster),
+            (SB_PAGES_PER_BLOCK, self.pages_per_block),
+            (SB_UNKNOWN, 0xff00),
+        ];
+
+        for (offset, value) in fields16 {
+            raw[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let fields32 = [
+            (SB_CLUSTERS_PER_CARD, self.clusters_per_card),
+            (SB_ALLOC_OFFSET, self.alloc_offset),
+            (SB_ALLOC_END, self.alloc_end),
+            (SB_ROOTDIR_CLUSTER, self.rootdir_cluster),
+            (SB_BACKUP_BLOCK1, self.backup_block1),
+            (SB_BACKUP_BLOCK2, self.backup_block2),
+        ];
+
+        for (offset, value) in fields32 {
+            raw[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        for (i, cluster) in self.ifc_list.iter().take(SUPERBLOCK_MAX_IFC).enumerate() {
+            let offset = SB_IFC_LIST + i * 4;
+            raw[offset..offset + 4].copy_from_slice(&cluster.to_le_bytes());
+        }
+
+        // No bad blocks on a virtual card.
+        let bad_blocks = SB_BAD_BLOCK_LIST..SB_BAD_BLOCK_LIST + SUPERBLOCK_MAX_BAD_BLOCKS * 4;
+        raw[bad_blocks].fill(0xff);
+        raw[SB_CARD_TYPE] = CARD_TYPE_PS2;
+        raw[SB_CARD_FLAGS] = CARD_FLAGS;
+
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_8mb_card_layout() {
+        // Values found on official 8MB memory cards.
+        let superblock = Superblock::new(512, 2, 16, 8192);
+        assert_eq!(superblock.ifc_list, vec![8]);
+        assert_eq!(superblock.alloc_offset, 41);
+        assert_eq!(superblock.alloc_end, 8135);
+        assert_eq!(superblock.backup_block1, 1023);
+        assert_eq!(superblock.backup_block2, 1022);
+    }
+
+    #[test]
+    fn test_superblock_roundtrip() {
+        let superblock = Superblock::new(512, 2, 16, 65536);
+        let parsed = Superblock::parse(&superblock.to_bytes()).unwrap();
+        assert_eq!(parsed.clusters_per_card, 65536);
+        assert_eq!(parsed.alloc_offset, superblock.alloc_offset);
+        assert_eq!(parsed.ifc_list, superblock.ifc_list);
+        assert!(Superblock::parse(&[0x00; SUPERBLOCK_SIZE]).is_err());
+    }
+}